@@ -1,12 +1,13 @@
 use clap::Parser;
+use std::collections::HashSet;
+use std::process::Command;
 
 /// Rusty Hello - CLI arguments et ownership
 #[derive(Parser, Debug)]
 #[command(name = "hello", version = "1.0", about = "Génère des salutations")]
 struct Args {
-    /// Name to greet
-    #[arg(default_value = "World")]
-    name: String,
+    /// Names to greet [default: World]
+    names: Vec<String>,
 
     /// Convert to uppercase
     #[arg(short, long)]
@@ -15,21 +16,125 @@ struct Args {
     /// Repeat greeting N times
     #[arg(short, long, default_value_t = 1)]
     repeat: u8,
+
+    /// Without names, greet `git config user.name` (falls back to World)
+    #[arg(long)]
+    from_git: bool,
+
+    /// Print how many names were greeted and lines printed
+    #[arg(long)]
+    summary: bool,
+}
+
+/// Nom configuré dans git, ou None si git est absent ou non configuré
+fn git_user_name() -> Option<String> {
+    let output = Command::new("git").args(["config", "user.name"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_git_name(&output.stdout)
+}
+
+/// Sortie de `git config user.name` : le nom sans espaces autour, None si vide
+fn parse_git_name(stdout: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(stdout).ok()?.trim().to_string();
+    if name.is_empty() { None } else { Some(name) }
+}
+
+/// Les noms à saluer : ceux donnés, sinon le nom git (--from-git), sinon World
+fn resolve_names(names: Vec<String>, from_git: bool, git_name: impl FnOnce() -> Option<String>) -> Vec<String> {
+    if !names.is_empty() {
+        names
+    } else if let Some(name) = from_git.then(git_name).flatten() {
+        vec![name]
+    } else {
+        vec!["World".to_string()]
+    }
+}
+
+/// Les lignes à afficher : la salutation de chaque nom, répétée `repeat` fois
+fn greetings(names: &[String], upper: bool, repeat: u8) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for name in names {
+        // On prépare le message
+        let mut greeting = format!("Hello, {}!", name);
+
+        // Manipulation de la String (Ownership/Mutabilité)
+        if upper {
+            greeting = greeting.to_uppercase();
+        }
+
+        // Répétition
+        for _ in 0..repeat {
+            lines.push(greeting.clone());
+        }
+    }
+
+    lines
+}
+
+/// Ligne --summary ; doublons comptés une seule fois
+fn summary_line(names: &[String], lines: usize) -> String {
+    let unique: HashSet<&String> = names.iter().collect();
+    format!(
+        "Greeted {} unique name{}, printed {} line{}",
+        unique.len(),
+        if unique.len() == 1 { "" } else { "s" },
+        lines,
+        if lines == 1 { "" } else { "s" }
+    )
 }
 
 fn main() {
     let args = Args::parse();
 
-    // On prépare le message
-    let mut greeting = format!("Hello, {}!", args.name);
+    let names = resolve_names(args.names, args.from_git, git_user_name);
+
+    let lines = greetings(&names, args.upper, args.repeat);
+    for line in &lines {
+        println!("{}", line);
+    }
+
+    if args.summary {
+        println!("{}", summary_line(&names, lines.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Manipulation de la String (Ownership/Mutabilité)
-    if args.upper {
-        greeting = greeting.to_uppercase();
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|n| n.to_string()).collect()
     }
 
-    // Répétition
-    for _ in 0..args.repeat {
-        println!("{}", greeting);
+    #[test]
+    fn summary_counts_unique_names_and_repeated_lines() {
+        let names = names(&["Ann", "Bob", "Ann"]);
+        let lines = greetings(&names, false, 2);
+
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "Hello, Ann!");
+        assert_eq!(lines[2], "Hello, Bob!");
+        assert_eq!(summary_line(&names, lines.len()), "Greeted 2 unique names, printed 6 lines");
+
+        assert_eq!(summary_line(&names[..1], 1), "Greeted 1 unique name, printed 1 line");
+    }
+
+    #[test]
+    fn from_git_greets_the_configured_name() {
+        let git_name = parse_git_name(b"  Ada Lovelace\n");
+        let names = resolve_names(vec![], true, || git_name);
+
+        assert_eq!(greetings(&names, false, 1), ["Hello, Ada Lovelace!"]);
+    }
+
+    #[test]
+    fn from_git_falls_back_to_world() {
+        assert_eq!(parse_git_name(b"\n"), None);
+        assert_eq!(resolve_names(vec![], true, || None), ["World"]);
+        // Explicit names win, git isn't even asked
+        assert_eq!(resolve_names(names(&["Bob"]), true, || panic!("git called")), ["Bob"]);
     }
-}
\ No newline at end of file
+}
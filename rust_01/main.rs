@@ -1,30 +1,499 @@
-use clap::Parser;
-use std::collections::HashMap;
+use clap::{Parser, ValueEnum};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Compteur de fréquence des mots avec HashMap et itérateurs
 #[derive(Parser, Debug)]
-#[command(name = "wordfreq", version = "1.0", about = "Count word frequency in text")]
+#[command(name = "wordfreq", version = "1.0", about = "Count word frequency in text", disable_help_flag = true)]
 struct Args {
     /// Text to analyze (or use stdin)
     text: Option<String>,
 
+    /// Read the text from a file (takes precedence over TEXT)
+    #[arg(short = 'i', long = "file", value_name = "PATH")]
+    file: Option<String>,
+
     /// Show top N words [default: 10]
     #[arg(short, long, default_value_t = 10)]
     top: usize,
 
-    /// Ignore words shorter than N [default: 1]
+    /// Show the N least frequent words instead
+    #[arg(long, conflicts_with = "top")]
+    bottom: Option<usize>,
+
+    /// Ignore words shorter than N characters, measured after case folding [default: 1]
     #[arg(short, long, default_value_t = 1)]
     min_length: usize,
 
+    /// Hide words counted fewer than K times [default: 1]
+    #[arg(long, value_name = "K", default_value_t = 1)]
+    min_count: u32,
+
     /// Ignore case
     #[arg(long)]
     ignore_case: bool,
 
+    /// Drop tokens made only of digits
+    #[arg(long)]
+    no_numbers: bool,
+
+    /// Count sequences of N consecutive words instead of single words [default: 1]
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    ngram: u16,
+
+    /// Also split on zero-width separators (Unicode whitespace semantics)
+    #[arg(long)]
+    unicode_whitespace: bool,
+
+    /// Word splitting: regex (whitespace, punctuation stripped) or unicode (UAX #29 word boundaries)
+    #[arg(long, value_enum, default_value_t = WordSplit::Regex)]
+    words: WordSplit,
+
+    /// Count characters (Unicode scalar values) instead of words
+    #[arg(long)]
+    chars: bool,
+
+    /// With --chars, don't count whitespace characters
+    #[arg(long)]
+    skip_whitespace: bool,
+
+    /// Drop this word from the count (repeatable, respects --ignore-case)
+    #[arg(long = "exclude", value_name = "WORD")]
+    exclude: Vec<String>,
+
+    /// Drop every word listed in FILE (one per line, respects --ignore-case)
+    #[arg(long, value_name = "FILE")]
+    stop_words: Option<String>,
+
+    /// Count stop words after all (cancels an earlier --stop-words)
+    #[arg(long, overrides_with = "stop_words")]
+    no_stop_words: bool,
+
+    /// Only list words whose share of all counted words exceeds F percent
+    #[arg(long)]
+    above_percent: Option<f64>,
+
+    /// List every counted word in dictionary order (ignores --top/--bottom)
+    #[arg(long)]
+    alphabetical: bool,
+
+    /// Output format: text, json or csv [default: text]
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Show each word's share of all counted tokens
+    #[arg(long)]
+    percent: bool,
+
+    /// Draw a bar chart next to the listed words
+    #[arg(long)]
+    chart: bool,
+
+    /// Columns used by the longest --chart bar [default: 40]
+    #[arg(long, default_value_t = 40)]
+    chart_width: usize,
+
+    /// Also print total, unique and average counts
+    #[arg(long)]
+    stats: bool,
+
+    /// Report words that appear in several casings (without --ignore-case)
+    #[arg(long, conflicts_with = "ignore_case")]
+    case_report: bool,
+
+    /// Print every occurrence of WORD with its neighbours instead of counting
+    #[arg(long, value_name = "WORD")]
+    context: Option<String>,
+
+    /// Neighbour words shown on each side by --context [default: 3]
+    #[arg(long, default_value_t = 3)]
+    window: usize,
+
+    /// Summarize how many tokens are lowercase, UPPERCASE, Title case or MiXed
+    #[arg(long)]
+    letter_case_stats: bool,
+
+    /// Emit a synthetic Zipf-distributed corpus of N words (benchmarking)
+    #[arg(long, hide = true)]
+    generate_corpus: Option<usize>,
+
+    /// Seed for --generate-corpus
+    #[arg(long, hide = true, default_value_t = 42)]
+    seed: u64,
+
     /// Help
     #[arg(short, long)]
     help: bool,
 }
 
+/// Text to analyze: the --file contents, else the TEXT argument, else stdin
+fn input_text(args: &Args) -> Result<String, String> {
+    if let Some(path) = &args.file {
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))
+    } else if let Some(t) = &args.text {
+        Ok(t.clone())
+    } else {
+        // Read from stdin
+        use std::io::{self, Read};
+        let mut buffer = String::new();
+        io::stdin()
+            .read_to_string(&mut buffer)
+            .map_err(|e| format!("Cannot read stdin: {}", e))?;
+        Ok(buffer)
+    }
+}
+
+/// Raw tokens in text order, as split by the chosen --words mode
+fn tokenize(text: &str, split: WordSplit, unicode_whitespace: bool) -> Vec<&str> {
+    match split {
+        WordSplit::Unicode => text.unicode_words().collect(),
+        WordSplit::Regex => split_words(text, unicode_whitespace),
+    }
+}
+
+/// Clean punctuation (unicode words already exclude it, apostrophes inside words are kept)
+fn clean_word(word: &str, split: WordSplit) -> String {
+    match split {
+        WordSplit::Unicode => word.to_string(),
+        WordSplit::Regex => word.chars().filter(|c| c.is_alphanumeric()).collect(),
+    }
+}
+
+/// Bar length for `count`, scaled so that `max` fills `width` columns
+fn bar_length(count: u32, max: u32, width: usize) -> usize {
+    if max == 0 {
+        return 0;
+    }
+    (count as usize * width + max as usize / 2) / max as usize
+}
+
+/// How text is cut into words
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum WordSplit {
+    /// Whitespace-separated tokens with every non-alphanumeric character removed
+    /// (the original splitting, also accepted as "simple")
+    #[value(alias = "simple")]
+    Regex,
+    /// Unicode word boundaries: "don't" and "état" stay whole, "state-of-the-art" is four words
+    Unicode,
+}
+
+/// How the frequency list is printed
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// "word: count" lines
+    Text,
+    /// Array of {"word": ..., "count": N} objects
+    Json,
+    /// "word,count" header then one row per word
+    Csv,
+}
+
+/// CSV field, quoted when it holds a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Number of distinct words in the generated corpus vocabulary
+const CORPUS_VOCABULARY: usize = 1000;
+
+/// Deterministic pseudo-word for a vocabulary rank (rank 0 is the most frequent)
+fn corpus_word(rank: usize) -> String {
+    const SYLLABLES: [&str; 8] = ["ka", "lo", "mi", "ne", "ru", "sa", "ti", "vo"];
+    let mut word = String::new();
+    let mut n = rank;
+    loop {
+        word.push_str(SYLLABLES[n % SYLLABLES.len()]);
+        n /= SYLLABLES.len();
+        if n == 0 {
+            break;
+        }
+        n -= 1;
+    }
+    word
+}
+
+/// Synthetic text whose word ranks follow a Zipf-like 1/rank distribution
+fn generate_corpus(words: usize, seed: u64) -> String {
+    let weights: Vec<f64> = (1..=CORPUS_VOCABULARY).map(|rank| 1.0 / rank as f64).collect();
+    let dist = WeightedIndex::new(&weights).expect("Zipf weights are positive");
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..words)
+        .map(|_| corpus_word(dist.sample(&mut rng)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Zero-width characters that are not Unicode White_Space but separate words in pasted text
+const ZERO_WIDTH_SEPARATORS: [char; 5] = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Split text into raw tokens. `split_whitespace` already follows White_Space
+/// (tabs, no-break spaces...); the Unicode mode also breaks on zero-width separators.
+fn split_words(text: &str, unicode_whitespace: bool) -> Vec<&str> {
+    if unicode_whitespace {
+        text.split(|c: char| c.is_whitespace() || ZERO_WIDTH_SEPARATORS.contains(&c))
+            .filter(|w| !w.is_empty())
+            .collect()
+    } else {
+        text.split_whitespace().collect()
+    }
+}
+
+/// Letter casing of a token, judged on its original spelling
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum CaseClass {
+    Lower,
+    Upper,
+    Title,
+    Mixed,
+    /// No letters at all (numbers)
+    NoLetters,
+}
+
+fn classify_case(word: &str) -> CaseClass {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    let Some((first, rest)) = letters.split_first() else {
+        return CaseClass::NoLetters;
+    };
+
+    if letters.iter().all(|c| !c.is_uppercase()) {
+        CaseClass::Lower
+    } else if letters.iter().all(|c| !c.is_lowercase()) {
+        // A single capital ("I", "A") counts as uppercase
+        CaseClass::Upper
+    } else if first.is_uppercase() && rest.iter().all(|c| !c.is_uppercase()) {
+        CaseClass::Title
+    } else {
+        CaseClass::Mixed
+    }
+}
+
+/// Number of tokens in each casing class, punctuation stripped
+fn letter_case_counts(text: &str, unicode_whitespace: bool) -> HashMap<CaseClass, u32> {
+    let mut stats = HashMap::new();
+    for word in split_words(text, unicode_whitespace) {
+        let word: String = word.chars().filter(|c| c.is_alphanumeric()).collect();
+        if !word.is_empty() {
+            *stats.entry(classify_case(&word)).or_insert(0) += 1;
+        }
+    }
+    stats
+}
+
+/// Group case-sensitive counts by lowercased key, keeping groups with 2+ casings
+fn case_variants(word_freq: &HashMap<String, u32>) -> Vec<(String, Vec<(&str, u32)>)> {
+    let mut groups: HashMap<String, Vec<(&str, u32)>> = HashMap::new();
+    for (word, &count) in word_freq {
+        groups.entry(word.to_lowercase()).or_default().push((word.as_str(), count));
+    }
+
+    let mut report: Vec<_> = groups
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .collect();
+    for (_, variants) in report.iter_mut() {
+        variants.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    }
+    report.sort_by(|a, b| a.0.cmp(&b.0));
+    report
+}
+
+/// --exclude words and the lines of the stop-word list (`stop_words`), folded
+/// the same way as the counted keys
+fn excluded_words(args: &Args, stop_words: &str) -> HashSet<String> {
+    args.exclude
+        .iter()
+        .map(String::as_str)
+        .chain(stop_words.lines().map(str::trim).filter(|w| !w.is_empty()))
+        .map(|w| if args.ignore_case { w.to_lowercase() } else { w.to_string() })
+        .collect()
+}
+
+/// Frequency of every kept word (or n-gram, or character with --chars), keyed
+/// as counted: cleaned, folded by --ignore-case and filtered
+fn count_tokens(text: &str, args: &Args, excluded: &HashSet<String>) -> HashMap<String, u32> {
+    // Count word frequencies using HashMap
+    let mut word_freq: HashMap<String, u32> = HashMap::new();
+
+    if args.chars {
+        for c in text.chars() {
+            if args.skip_whitespace && c.is_whitespace() {
+                continue;
+            }
+            // Lowercasing a char can yield several (e.g. 'İ'), so keys stay strings
+            let key = if args.ignore_case {
+                c.to_lowercase().collect()
+            } else {
+                c.to_string()
+            };
+            if excluded.contains(&key) {
+                continue;
+            }
+            *word_freq.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    let words = if args.chars {
+        Vec::new()
+    } else {
+        tokenize(text, args.words, args.unicode_whitespace)
+    };
+    let mut kept = Vec::new();
+    for word in words {
+        let word = clean_word(word, args.words);
+
+        // "2024" goes, "rust2024" stays
+        if args.no_numbers && !word.is_empty() && word.chars().all(char::is_numeric) {
+            continue;
+        }
+
+        let word_key = if args.ignore_case {
+            word.to_lowercase()
+        } else {
+            word
+        };
+
+        // Filter on the final key: lowercasing can change the length (e.g. 'İ')
+        if word_key.chars().count() < args.min_length || excluded.contains(&word_key) {
+            continue;
+        }
+
+        kept.push(word_key);
+    }
+
+    // Each window of N consecutive kept words is one key (N = 1: single words)
+    for gram in kept.windows(args.ngram as usize) {
+        *word_freq.entry(gram.join(" ")).or_insert(0) += 1;
+    }
+
+    word_freq
+}
+
+/// Entries to list, in output order: filtered by --min-count and
+/// --above-percent, sorted and cut to --top / --bottom
+fn rank<'a>(word_freq: &'a HashMap<String, u32>, args: &Args) -> Vec<(&'a String, &'a u32)> {
+    // Share of the whole corpus, computed before any filtering
+    let total: u32 = word_freq.values().sum();
+    let share = |count: u32| count as f64 * 100.0 / total as f64;
+
+    let mut freq_vec: Vec<_> = word_freq
+        .iter()
+        .filter(|(_, &count)| count >= args.min_count)
+        .filter(|(_, &count)| args.above_percent.is_none_or(|p| share(count) > p))
+        .collect();
+    let limit = if args.alphabetical {
+        // Concordance: every word, keyed as counted (already folded by --ignore-case)
+        freq_vec.sort_by(|a, b| a.0.cmp(b.0));
+        freq_vec.len()
+    } else if let Some(bottom) = args.bottom {
+        freq_vec.sort_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)));
+        bottom
+    } else {
+        // Ties break alphabetically so the output is reproducible
+        freq_vec.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        args.top
+    };
+    freq_vec.truncate(limit);
+    freq_vec
+}
+
+/// The listed entries in the chosen --format; `total` is the number of
+/// counted tokens that --percent shares are taken of
+fn render_results(freq_vec: &[(&String, &u32)], total: u32, args: &Args) -> String {
+    // Share of the whole corpus, computed before any filtering
+    let share = |count: u32| count as f64 * 100.0 / total as f64;
+    let key_name = if args.chars { "char" } else { "word" };
+    let mut out = String::new();
+    match args.format {
+        OutputFormat::Json => {
+            let entries: Vec<_> = freq_vec
+                .iter()
+                .map(|(key, count)| json!({ key_name: key, "count": count }))
+                .collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&entries).expect("JSON serialization")).unwrap();
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{},count", key_name).unwrap();
+            for (key, count) in freq_vec {
+                writeln!(out, "{},{}", csv_field(key), count).unwrap();
+            }
+        }
+        OutputFormat::Text => {
+            // Share of every counted token, not just of the lines shown
+            let percent = |count: u32| {
+                if args.percent {
+                    format!(" ({:.1}%)", share(count))
+                } else {
+                    String::new()
+                }
+            };
+            let lines: Vec<(String, u32)> = freq_vec
+                .iter()
+                .map(|(key, &count)| {
+                    // Quote characters so that whitespace and control characters stay visible
+                    let label = if args.chars { format!("'{}'", key.escape_debug()) } else { key.to_string() };
+                    (format!("{}: {}{}", label, count, percent(count)), count)
+                })
+                .collect();
+
+            writeln!(out, "{}", if args.chars { "Character frequency:" } else { "Word frequency:" }).unwrap();
+            if args.chart {
+                let max = lines.iter().map(|&(_, count)| count).max().unwrap_or(0);
+                let pad = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or(0);
+                for (line, count) in &lines {
+                    let bar = "█".repeat(bar_length(*count, max, args.chart_width));
+                    writeln!(out, "{:<pad$} {}", line, bar, pad = pad).unwrap();
+                }
+            } else {
+                for (line, _) in &lines {
+                    writeln!(out, "{}", line).unwrap();
+                }
+            }
+        }
+    }
+    out
+}
+
+/// One line per occurrence of `target`: the raw token with up to --window
+/// neighbours on each side. Matching is on the cleaned (and folded) token.
+fn keyword_in_context(text: &str, target: &str, args: &Args) -> Vec<String> {
+    let fold = |w: String| if args.ignore_case { w.to_lowercase() } else { w };
+    let target = fold(clean_word(target, args.words));
+    let tokens = tokenize(text, args.words, args.unicode_whitespace);
+    let mut lines = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        if fold(clean_word(token, args.words)) != target {
+            continue;
+        }
+        let from = i.saturating_sub(args.window);
+        let to = (i + args.window + 1).min(tokens.len());
+        lines.push(tokens[from..to].join(" "));
+    }
+    lines
+}
+
+/// --stats line over the whole map, before --top: already filtered by
+/// --min-length and folded by --ignore-case
+fn summary_line(word_freq: &HashMap<String, u32>, key_name: &str) -> String {
+    let total: u32 = word_freq.values().sum();
+    let unique = word_freq.len();
+    let average = if unique > 0 { total as f64 / unique as f64 } else { 0.0 };
+    format!(
+        "Total: {} {}s, {} unique, {:.2} occurrences per unique {}",
+        total, key_name, unique, average, key_name
+    )
+}
+
 fn main() {
     let args = Args::parse();
 
@@ -37,54 +506,408 @@ fn main() {
         println!("  Text to analyze (or use stdin)");
         println!();
         println!("Options:");
+        println!("  -i, --file PATH     Read the text from a file");
         println!("  --top N             Show top N words [default: 10]");
-        println!("  --min-length N      Ignore words shorter than N [default: 1]");
+        println!("  --bottom N          Show the N least frequent words");
+        println!("  --min-length N      Ignore words shorter than N chars (after --ignore-case) [default: 1]");
+        println!("  --min-count K       Hide words seen fewer than K times [default: 1]");
         println!("  --ignore-case       Ignore case");
+        println!("  --no-numbers        Ignore purely numeric tokens");
+        println!("  --ngram N           Count runs of N consecutive words [default: 1]");
+        println!("  --unicode-whitespace  Also split on zero-width spaces");
+        println!("  --words MODE        Word splitting: regex or unicode (keeps \"don't\") [default: regex]");
+        println!("  --chars             Count characters instead of words");
+        println!("  --skip-whitespace   With --chars, ignore whitespace characters");
+        println!("  --exclude WORD      Drop WORD from the count (repeatable)");
+        println!("  --stop-words FILE   Drop the words listed in FILE (one per line)");
+        println!("  --no-stop-words     Don't apply --stop-words");
+        println!("  --above-percent F   Only show words above F% of all words");
+        println!("  --format FORMAT     Output as text, json or csv [default: text]");
+        println!("  --percent           Show each word's share of all counted words");
+        println!("  --chart             Draw a bar chart of the listed words");
+        println!("  --chart-width N     Longest bar length for --chart [default: 40]");
+        println!("  --stats             Print total / unique / average counts");
+        println!("  --alphabetical      List all words in dictionary order (ignores --top)");
+        println!("  --context WORD      Show each occurrence of WORD with surrounding words");
+        println!("  --window N          Words shown on each side with --context [default: 3]");
+        println!("  --letter-case-stats Count lowercase / UPPERCASE / Title / MiXed tokens");
+        println!("  --case-report       List words appearing in several casings");
         println!("  -h, --help          Print help");
         return;
     }
 
+    if let Some(words) = args.generate_corpus {
+        println!("{}", generate_corpus(words, args.seed));
+        return;
+    }
+
     // Get input text
-    let text = if let Some(t) = args.text {
-        t
-    } else {
-        // Read from stdin
-        use std::io::{self, Read};
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer).expect("Failed to read stdin");
-        buffer
+    let text = match input_text(&args) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     };
 
-    // Count word frequencies using HashMap
-    let mut word_freq: HashMap<String, u32> = HashMap::new();
+    if let Some(target) = &args.context {
+        let lines = keyword_in_context(&text, target, &args);
+        for line in &lines {
+            println!("{}", line);
+        }
+        let target = clean_word(target, args.words);
+        let target = if args.ignore_case { target.to_lowercase() } else { target };
+        println!("{} occurrence(s) of \"{}\"", lines.len(), target);
+        return;
+    }
 
-    for word in text.split_whitespace() {
-        // Clean punctuation
-        let word = word
-            .chars()
-            .filter(|c| c.is_alphanumeric())
-            .collect::<String>();
+    if args.letter_case_stats {
+        let stats = letter_case_counts(&text, args.unicode_whitespace);
+        let total: u32 = stats.values().sum();
+        println!("Letter case:");
+        for (class, name) in [
+            (CaseClass::Lower, "lowercase"),
+            (CaseClass::Upper, "UPPERCASE"),
+            (CaseClass::Title, "Title case"),
+            (CaseClass::Mixed, "MiXed case"),
+            (CaseClass::NoLetters, "no letters"),
+        ] {
+            let count = stats.get(&class).copied().unwrap_or(0);
+            let percent = if total > 0 { count as f64 * 100.0 / total as f64 } else { 0.0 };
+            println!("{:<12} {:>6} {:>6.1}%", name, count, percent);
+        }
+        println!("{:<12} {:>6}", "total", total);
+        return;
+    }
 
-        if word.len() < args.min_length {
-            continue;
+    let stop_words = match &args.stop_words {
+        Some(path) if !args.no_stop_words => match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error: Cannot read {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        _ => String::new(),
+    };
+
+    let excluded = excluded_words(&args, &stop_words);
+
+    let word_freq = count_tokens(&text, &args, &excluded);
+
+    if args.case_report {
+        println!("Case variants:");
+        for (key, variants) in case_variants(&word_freq) {
+            let listed: Vec<String> = variants
+                .iter()
+                .map(|(word, count)| format!("{} ({})", word, count))
+                .collect();
+            println!("{}: {}", key, listed.join(", "));
         }
+        return;
+    }
 
-        let word_key = if args.ignore_case {
-            word.to_lowercase()
-        } else {
-            word
-        };
+    let total: u32 = word_freq.values().sum();
+    let freq_vec = rank(&word_freq, &args);
+
+    print!("{}", render_results(&freq_vec, total, &args));
+
+    if args.stats {
+        let key_name = if args.chars { "char" } else { "word" };
+        let summary = summary_line(&word_freq, key_name);
+        // Keep JSON/CSV on stdout machine-readable
+        match args.format {
+            OutputFormat::Text => println!("{}", summary),
+            _ => eprintln!("{}", summary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpus_is_reproducible_and_rank_ordered() {
+        let corpus = generate_corpus(5000, 7);
+        assert_eq!(corpus, generate_corpus(5000, 7));
+        assert_ne!(corpus, generate_corpus(5000, 8));
+        assert_eq!(corpus.split(' ').count(), 5000);
+
+        let count = |word: &str| corpus.split(' ').filter(|w| *w == word).count();
+        assert!(count(&corpus_word(0)) > count(&corpus_word(1)));
+        assert!(count(&corpus_word(1)) > count(&corpus_word(50)));
+
+        // Every rank spells a different word
+        let words: HashSet<String> = (0..CORPUS_VOCABULARY).map(corpus_word).collect();
+        assert_eq!(words.len(), CORPUS_VOCABULARY);
+    }
+
+    #[test]
+    fn case_variants_group_spellings_of_one_word() {
+        let counts: HashMap<String, u32> = [("Rust", 2), ("rust", 5), ("RUST", 2), ("go", 3), ("Zed", 1)]
+            .into_iter()
+            .map(|(word, count)| (word.to_string(), count))
+            .collect();
+        let report = case_variants(&counts);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, "rust");
+        assert_eq!(report[0].1, [("rust", 5), ("RUST", 2), ("Rust", 2)]);
+        // Folded counts have no casings left to report
+        assert!(Args::try_parse_from(["wordfreq", "--case-report", "--ignore-case"]).is_err());
+    }
+
+    fn args(flags: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("wordfreq").chain(flags.iter().copied())).unwrap()
+    }
+
+    fn counts(text: &str, flags: &[&str]) -> HashMap<String, u32> {
+        count_tokens(text, &args(flags), &HashSet::new())
+    }
+
+    /// Listed (key, count) pairs in output order
+    fn ranked(text: &str, flags: &[&str]) -> Vec<(String, u32)> {
+        let args = args(flags);
+        let word_freq = count_tokens(text, &args, &HashSet::new());
+        rank(&word_freq, &args).into_iter().map(|(key, &count)| (key.clone(), count)).collect()
+    }
+
+    fn pairs(expected: &[(&str, u32)]) -> Vec<(String, u32)> {
+        expected.iter().map(|&(key, count)| (key.to_string(), count)).collect()
+    }
+
+    #[test]
+    fn above_percent_keeps_words_over_the_share() {
+        // 10 words: a is 50%, b 30%, c 20%
+        let text = "a a a a a b b b c c";
+        assert_eq!(ranked(text, &["--above-percent", "25"]), pairs(&[("a", 5), ("b", 3)]));
+        assert_eq!(ranked(text, &["--above-percent", "30"]), pairs(&[("a", 5)]));
+        // The share is of all words, not only of those left by --min-count
+        assert_eq!(ranked(text, &["--above-percent", "25", "--min-count", "4"]), pairs(&[("a", 5)]));
+    }
+
+    #[test]
+    fn ignore_case_folds_keys_before_the_length_check() {
+        assert_eq!(counts("Rust RUST rust", &["--ignore-case"]), HashMap::from([("rust".to_string(), 3)]));
+        assert_eq!(counts("Rust rust", &[]).len(), 2);
+
+        // 'İ' lowercases to two chars, so it passes --min-length 2 only once folded
+        assert!(counts("İ", &["--min-length", "2"]).is_empty());
+        assert_eq!(counts("İ", &["--ignore-case", "--min-length", "2"]), HashMap::from([("i\u{307}".to_string(), 1)]));
+    }
+
+    #[test]
+    fn excluded_words_are_dropped_from_the_count() {
+        let text = "The cat and THE dog and the bird";
+        let args = args(&["--exclude", "the", "--exclude", "and"]);
+        let counted = count_tokens(text, &args, &excluded_words(&args, ""));
+        assert_eq!(counted.get("THE"), Some(&1));
+        assert!(!counted.contains_key("the") && !counted.contains_key("and"));
+
+        let args = self::args(&["--ignore-case", "--exclude", "THE"]);
+        let counted = count_tokens(text, &args, &excluded_words(&args, ""));
+        assert!(!counted.contains_key("the"));
+        assert_eq!(counted.get("and"), Some(&2));
+    }
+
+    #[test]
+    fn whitespace_runs_and_zero_width_separators_split_words() {
+        assert_eq!(split_words("a \t\n b\u{00A0}c", false), ["a", "b", "c"]);
+        assert_eq!(split_words("one\u{200B}two  three", false), ["one\u{200B}two", "three"]);
+        assert_eq!(split_words("one\u{200B}two  three\u{FEFF}", true), ["one", "two", "three"]);
+        assert!(split_words(" \u{200B} ", true).is_empty());
+    }
+
+    #[test]
+    fn alphabetical_lists_all_words_in_order() {
+        let text = "pear apple fig apple banana pear apple";
+        let expected = pairs(&[("apple", 3), ("banana", 1), ("fig", 1), ("pear", 2)]);
+        assert_eq!(ranked(text, &["--alphabetical", "--top", "1"]), expected);
+        assert_eq!(ranked(text, &["--alphabetical", "--min-count", "2"]), pairs(&[("apple", 3), ("pear", 2)]));
+    }
+
+    #[test]
+    fn chars_mode_counts_characters() {
+        let counted = counts("Aa b!", &["--chars"]);
+        assert_eq!(counted.len(), 5);
+        assert_eq!((counted["A"], counted[" "], counted["!"]), (1, 1, 1));
+        assert_eq!(counts("Aa b", &["--chars", "--ignore-case", "--skip-whitespace"]),
+            HashMap::from([("a".to_string(), 2), ("b".to_string(), 1)]));
+        assert!(args(&["--chars"]).chars && !args(&[]).chars);
+    }
+
+    #[test]
+    fn letter_case_stats_classify_each_token() {
+        assert_eq!(classify_case("hello"), CaseClass::Lower);
+        assert_eq!(classify_case("NASA"), CaseClass::Upper);
+        assert_eq!(classify_case("I"), CaseClass::Upper);
+        assert_eq!(classify_case("Élan"), CaseClass::Title);
+        assert_eq!(classify_case("iPhone"), CaseClass::Mixed);
+        assert_eq!(classify_case("2024"), CaseClass::NoLetters);
+
+        let stats = letter_case_counts("the Cat SAT on the mAt, 42 ...", false);
+        assert_eq!(stats, HashMap::from([
+            (CaseClass::Lower, 3),
+            (CaseClass::Title, 1),
+            (CaseClass::Upper, 1),
+            (CaseClass::Mixed, 1),
+            (CaseClass::NoLetters, 1),
+        ]));
+    }
+
+    #[test]
+    fn file_input_takes_precedence_over_text() {
+        let path = std::env::temp_dir().join(format!("wordfreq_{}_input.txt", std::process::id()));
+        std::fs::write(&path, "from the file").unwrap();
+        let file = path.to_str().unwrap();
+        let from_file = input_text(&args(&["--file", file, "from the argument"]));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.as_deref(), Ok("from the file"));
+        assert_eq!(input_text(&args(&["from the argument"])).as_deref(), Ok("from the argument"));
+        assert!(input_text(&args(&["-i", file])).unwrap_err().starts_with("Cannot read "));
+        // -h/--help reach the custom help text instead of clap's
+        assert!(args(&["-h"]).help && args(&["--help"]).help);
+    }
+
+    fn rendered(text: &str, flags: &[&str]) -> String {
+        let args = args(flags);
+        let word_freq = count_tokens(text, &args, &HashSet::new());
+        render_results(&rank(&word_freq, &args), word_freq.values().sum(), &args)
+    }
+
+    #[test]
+    fn json_and_csv_list_the_ranked_words() {
+        let json: serde_json::Value = serde_json::from_str(&rendered("b a b", &["--format", "json"])).unwrap();
+        assert_eq!(json, json!([{ "word": "b", "count": 2 }, { "word": "a", "count": 1 }]));
+        let json: serde_json::Value = serde_json::from_str(&rendered("xy", &["--format", "json", "--chars"])).unwrap();
+        assert_eq!(json[0], json!({ "char": "x", "count": 1 }));
+
+        assert_eq!(rendered("b a b", &["--format", "csv"]), "word,count\nb,2\na,1\n");
+        assert_eq!(rendered("a,b", &["--format", "csv", "--chars", "--top", "1"]), "char,count\n\",\",1\n");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("plain"), "plain");
+    }
+
+    #[test]
+    fn stop_words_are_dropped_unless_cancelled() {
+        let args = args(&["--ignore-case"]);
+        let excluded = excluded_words(&args, "The\n  a \n\n# not special\n");
+        assert_eq!(excluded, HashSet::from(["the".to_string(), "a".to_string(), "# not special".to_string()]));
+        let text = "The cat saw the dog, the bird saw a cat";
+        // Without the list the stop word is the most frequent token
+        assert_eq!(rank(&count_tokens(text, &args, &HashSet::new()), &args)[0], (&"the".to_string(), &3));
+        let counted = count_tokens(text, &args, &excluded);
+        assert_eq!(counted.len(), 4);
+        assert!(!counted.contains_key("the") && !counted.contains_key("a"));
+        assert_eq!(rank(&counted, &args)[0], (&"cat".to_string(), &2));
+
+        let cancelled = self::args(&["--stop-words", "stop.txt", "--no-stop-words"]);
+        assert!(cancelled.no_stop_words && cancelled.stop_words.is_none());
+    }
+
+    #[test]
+    fn ngrams_count_runs_of_kept_words() {
+        assert_eq!(ranked("the cat the cat", &["--ngram", "2"]), pairs(&[("the cat", 2), ("cat the", 1)]));
+        assert!(counts("one two", &["--ngram", "3"]).is_empty());
+        // Filtered words are skipped before the windows are formed
+        assert_eq!(counts("big a cat", &["--ngram", "2", "--min-length", "2"]), HashMap::from([("big cat".to_string(), 1)]));
+        assert!(Args::try_parse_from(["wordfreq", "--ngram", "0"]).is_err());
+    }
+
+    #[test]
+    fn ties_break_alphabetically() {
+        let text = "pear fig apple fig apple pear kiwi zest date";
+        assert_eq!(ranked(text, &["--top", "3"]), pairs(&[("apple", 2), ("fig", 2), ("pear", 2)]));
+        // Words seen once are ordered the same way
+        assert_eq!(ranked(text, &["--top", "6"])[3..], pairs(&[("date", 1), ("kiwi", 1), ("zest", 1)]));
+        for _ in 0..5 {
+            assert_eq!(ranked(text, &[]), ranked(text, &[]));
+        }
+    }
+
+    #[test]
+    fn bottom_lists_least_frequent_first() {
+        let text = "a a a b b c d";
+        assert_eq!(ranked(text, &["--bottom", "3"]), pairs(&[("c", 1), ("d", 1), ("b", 2)]));
+        assert!(Args::try_parse_from(["wordfreq", "--bottom", "2", "--top", "2"]).is_err());
+    }
+
+    #[test]
+    fn stats_line_counts_total_unique_and_average() {
+        let word_freq = counts("a b a c a b", &[]);
+        assert_eq!(summary_line(&word_freq, "word"), "Total: 6 words, 3 unique, 2.00 occurrences per unique word");
+        assert_eq!(summary_line(&HashMap::new(), "char"), "Total: 0 chars, 0 unique, 0.00 occurrences per unique char");
+    }
+
+    #[test]
+    fn no_numbers_drops_digit_only_tokens() {
+        let counted = counts("hello 123 world 456 rust2024 (789)", &["--no-numbers"]);
+        let mut keys: Vec<&str> = counted.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["hello", "rust2024", "world"]);
+        assert_eq!(counts("hello 123", &[]).len(), 2);
+    }
+
+    #[test]
+    fn percent_is_the_share_of_every_counted_word() {
+        assert_eq!(rendered("a a a b", &["--percent"]), "Word frequency:\na: 3 (75.0%)\nb: 1 (25.0%)\n");
+        // Shares stay relative to the whole text when fewer words are shown
+        assert_eq!(rendered("a a a b", &["--percent", "--top", "1"]), "Word frequency:\na: 3 (75.0%)\n");
+        assert_eq!(rendered("a a a b", &[]), "Word frequency:\na: 3\nb: 1\n");
+
+        // Rounded to one decimal, every share listed still adds up to the whole
+        let output = rendered("a a b c c c d", &["--percent"]);
+        let shares: Vec<f64> = output
+            .lines()
+            .filter_map(|line| line.split_once(" (")?.1.strip_suffix("%)")?.parse().ok())
+            .collect();
+        assert_eq!(shares.len(), 4);
+        assert!((shares.iter().sum::<f64>() - 100.0).abs() <= 0.05 * shares.len() as f64);
+    }
+
+    #[test]
+    fn min_count_hides_rare_words() {
+        let text = "a a a b b c";
+        assert_eq!(ranked(text, &["--min-count", "2"]), pairs(&[("a", 3), ("b", 2)]));
+        assert!(ranked(text, &["--min-count", "4"]).is_empty());
+        assert_eq!(ranked(text, &[]).len(), 3);
+    }
+
+    #[test]
+    fn word_modes_split_differently() {
+        let text = "Don't stop: state-of-the-art état!";
+        let mut regex: Vec<String> = counts(text, &[]).into_keys().collect();
+        regex.sort();
+        assert_eq!(regex, ["Dont", "stateoftheart", "stop", "état"]);
+
+        let mut unicode: Vec<String> = counts(text, &["--words", "unicode"]).into_keys().collect();
+        unicode.sort();
+        assert_eq!(unicode, ["Don't", "art", "of", "state", "stop", "the", "état"]);
+
+        assert_eq!(args(&[]).words, WordSplit::Regex);
+        assert_eq!(args(&["--words", "simple"]).words, WordSplit::Regex);
+    }
+
+    #[test]
+    fn chart_bars_scale_to_the_largest_count() {
+        assert_eq!(bar_length(10, 10, 40), 40);
+        assert_eq!(bar_length(1, 3, 10), 3);
+        assert_eq!(bar_length(2, 3, 10), 7);
+        assert_eq!(bar_length(0, 0, 10), 0);
 
-        *word_freq.entry(word_key).or_insert(0) += 1;
+        let chart = rendered("aa aa aa aa b b", &["--chart", "--chart-width", "4"]);
+        assert_eq!(chart, "Word frequency:\naa: 4 ████\nb: 2  ██\n");
     }
 
-    // Sort by frequency and collect top N
-    let mut freq_vec: Vec<_> = word_freq.iter().collect();
-    freq_vec.sort_by(|a, b| b.1.cmp(a.1));
+    #[test]
+    fn context_shows_neighbours_of_each_occurrence() {
+        let args = args(&["--window", "1"]);
+        assert_eq!(keyword_in_context("the big cat sat", "cat", &args), ["big cat sat"]);
+        assert_eq!(keyword_in_context("Cat, the cat.", "cat", &args), ["the cat."]);
 
-    // Print results
-    println!("Word frequency:");
-    for (word, count) in freq_vec.iter().take(args.top) {
-        println!("{}: {}", word, count);
+        let args = self::args(&["--window", "1", "--ignore-case"]);
+        assert_eq!(keyword_in_context("Cat, the cat.", "CAT", &args), ["Cat, the", "the cat."]);
+        assert!(keyword_in_context("no match here", "cat", &args).is_empty());
     }
 }
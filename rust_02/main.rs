@@ -1,13 +1,15 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::cmp::Reverse;
 use std::fs;
 use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::time::Instant;
 
 /// Outil hexadécimal pour lire et écrire des fichiers binaires
 #[derive(Parser, Debug)]
-#[command(name = "hextool", version = "1.0", about = "Read & Write binary files in hexadecimal")]
+#[command(name = "hextool", version = "1.0", about = "Read & Write binary files in hexadecimal", disable_help_flag = true)]
 struct Args {
-    /// Fichier cible
-    #[arg(short, long)]
+    /// Fichier cible ("-" pour stdin en lecture)
+    #[arg(short, long, required_unless_present = "help", default_value = "")]
     file: String,
 
     /// Mode lecture (affiche hex)
@@ -18,20 +20,339 @@ struct Args {
     #[arg(short, long)]
     write: Option<String>,
 
-    /// Offset en bytes (décimal ou hex avec 0x)
-    #[arg(short, long, default_value = "0")]
+    /// Écrit le contenu brut d'un autre fichier (à la place de --write)
+    #[arg(long, value_name = "PATH", conflicts_with = "write")]
+    write_from: Option<String>,
+
+    /// Offset en bytes (décimal ou hex avec 0x, -N compte depuis la fin)
+    #[arg(short, long, default_value = "0", visible_alias = "offset-start", allow_hyphen_values = true)]
     offset: String,
 
     /// Nombre de bytes à lire
     #[arg(short, long)]
     size: Option<usize>,
 
+    /// Écrit le dump dans un fichier au lieu de stdout
+    #[arg(long, value_name = "FILE")]
+    out: Option<String>,
+
+    /// Table de substitution OLD:NEW,... appliquée en place
+    #[arg(long)]
+    translate: Option<String>,
+
+    /// Histogramme des bytes de la zone sélectionnée
+    #[arg(long)]
+    histogram: bool,
+
+    /// Ordre de l'histogramme (value ou count)
+    #[arg(long, value_enum, default_value_t = HistogramSort::Value)]
+    sort: HistogramSort,
+
+    /// Décode la zone comme un entier (u16, u32 ou u64) au lieu du dump
+    #[arg(long = "as", value_enum, value_name = "TYPE")]
+    as_int: Option<IntWidth>,
+
+    /// Endianness de --as (le ou be)
+    #[arg(long, value_enum, default_value_t = Endian::Le)]
+    endian: Endian,
+
+    /// Calcule une somme de contrôle de la zone (crc32, sum8 ou xor)
+    #[arg(long, value_enum)]
+    checksum: Option<Checksum>,
+
+    /// Cherche un motif hex et affiche chaque offset trouvé (à partir de --offset)
+    #[arg(long, value_name = "HEX")]
+    find: Option<String>,
+
+    /// Cherche une chaîne (comparée sur ses bytes UTF-8)
+    #[arg(long, value_name = "TEXT", conflicts_with = "find")]
+    find_str: Option<String>,
+
+    /// Remplace les occurrences de OLD par NEW (hex OLD:NEW)
+    #[arg(long, value_name = "OLD:NEW")]
+    replace: Option<String>,
+
+    /// Ne remplace que la première occurrence
+    #[arg(long, conflicts_with = "all")]
+    first_only: bool,
+
+    /// Autorise le remplacement de toutes les occurrences
+    #[arg(long)]
+    all: bool,
+
+    /// Pas de confirmation pour --all
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Compare le fichier à OTHER byte par byte, par blocs (fichiers de toute taille)
+    #[arg(long, value_name = "OTHER")]
+    diff: Option<String>,
+
+    /// Script d'édition (offset: old -> new) pour transformer le fichier en OTHER
+    #[arg(long, conflicts_with = "diff")]
+    diff_hex: Option<String>,
+
+    /// Regroupe les différences contiguës en plages (avec --diff ou --diff-hex)
+    #[arg(long)]
+    group_diffs: bool,
+
+    /// Affiche les deux fichiers côte à côte, bytes différents marqués (avec --diff ou --diff-hex)
+    #[arg(long, conflicts_with = "group_diffs")]
+    side_by_side: bool,
+
+    /// Applique un script d'édition produit par --diff-hex
+    #[arg(long)]
+    patch: Option<String>,
+
+    /// Taille des blocs lus sur stdin
+    #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+
+    /// Affiche le débit de lecture sur stdin
+    #[arg(long)]
+    progress: bool,
+
+    /// Nombre de bytes par ligne du dump
+    #[arg(long, default_value_t = DEFAULT_WIDTH)]
+    width: usize,
+
+    /// Choisit le plus grand nombre de bytes par ligne tenant dans le terminal
+    #[arg(long)]
+    auto_width: bool,
+
+    /// Hex en majuscules dans le dump (bytes et offsets)
+    #[arg(long)]
+    upper: bool,
+
+    /// Taille maximale chargée en mémoire (bytes)
+    #[arg(long, default_value_t = DEFAULT_MAX_FILE_SIZE)]
+    max_file_size: u64,
+
+    /// Complète une hex string de longueur impaire par un 0 (left ou right)
+    #[arg(long, value_enum, value_name = "SIDE", num_args = 0..=1, default_missing_value = "left")]
+    pad_odd: Option<PadSide>,
+
+    /// Écrit à la fin du fichier (ignore --offset)
+    #[arg(long, conflicts_with = "insert")]
+    append: bool,
+
+    /// Insère les bytes à l'offset en décalant la suite du fichier
+    #[arg(long)]
+    insert: bool,
+
+    /// Inverse l'ordre des bytes (lecture et écriture)
+    #[arg(long)]
+    reverse_bytes: bool,
+
+    /// Extrait les chaînes ASCII imprimables
+    #[arg(long)]
+    strings: bool,
+
+    /// Nombre maximum de chaînes affichées
+    #[arg(long)]
+    limit: Option<usize>,
+
     /// Aide
     #[arg(short, long)]
     help: bool,
 }
 
-fn parse_offset(offset_str: &str) -> Result<u64, String> {
+/// Bytes per dump line when no width is given or the terminal can't be measured
+const DEFAULT_WIDTH: usize = 16;
+
+/// Default buffer size for stdin reads (64 KiB)
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Default cap on how much of a file is loaded into memory (256 MiB)
+const DEFAULT_MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// How input is loaded: stdin chunking for "-" and the in-memory size cap
+#[derive(Clone, Copy)]
+struct ReadOptions {
+    chunk_size: usize,
+    progress: bool,
+    max_size: u64,
+}
+
+/// How dump lines are laid out
+#[derive(Clone, Copy)]
+struct DumpLayout {
+    width: usize,
+    /// Uppercase hex digits for bytes and offsets
+    upper: bool,
+}
+
+/// Refuse to load more than `limit` bytes into memory
+fn check_load_size(len: u64, limit: u64) -> Result<(), String> {
+    if len > limit {
+        return Err(format!(
+            "Refusing to load {} bytes (limit {}); select a range with --size or raise --max-file-size",
+            len, limit
+        ));
+    }
+    Ok(())
+}
+
+/// Whole-file read guarded by the size cap
+fn read_whole_file(filename: &str, limit: u64) -> Result<Vec<u8>, String> {
+    let len = fs::metadata(filename)
+        .map_err(|e| format!("Cannot read {}: {}", filename, e))?
+        .len();
+    check_load_size(len, limit)?;
+    fs::read(filename).map_err(|e| format!("Cannot read {}: {}", filename, e))
+}
+
+/// Minimum run of printable bytes reported by --strings
+const MIN_STRING_LEN: usize = 4;
+
+/// Where --write puts its bytes
+#[derive(Clone, Copy)]
+enum WriteMode {
+    /// Replace the bytes at the offset
+    Overwrite,
+    /// Add the bytes after the current end of file, ignoring the offset
+    Append,
+    /// Put the bytes at the offset and shift the rest of the file forward
+    Insert,
+}
+
+/// Side where --pad-odd adds the missing zero nibble
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PadSide {
+    /// "abc" -> 0a bc
+    Left,
+    /// "abc" -> ab c0
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum HistogramSort {
+    /// Byte value order (00..ff)
+    Value,
+    /// Most frequent first
+    Count,
+}
+
+/// --offset as given: from the start, or (with a leading '-') back from the end
+#[derive(Clone, Copy, Debug)]
+enum Offset {
+    Start(u64),
+    End(u64),
+}
+
+impl Offset {
+    /// Absolute position in a file of `file_size` bytes
+    fn resolve(self, file_size: u64) -> Result<u64, String> {
+        match self {
+            Offset::Start(offset) => Ok(offset),
+            Offset::End(back) if back > file_size => Err(format!(
+                "Offset -0x{:x} is before the start of the file ({} bytes)",
+                back, file_size
+            )),
+            Offset::End(back) => Ok(file_size - back),
+        }
+    }
+
+    fn seek_from(self) -> SeekFrom {
+        match self {
+            Offset::Start(offset) => SeekFrom::Start(offset),
+            Offset::End(back) => SeekFrom::End(-(back as i64)),
+        }
+    }
+}
+
+/// Absolute offset for commands that work on plain positions
+fn absolute_offset(filename: &str, offset: Offset) -> Result<u64, String> {
+    match offset {
+        Offset::Start(offset) => Ok(offset),
+        Offset::End(_) if filename == "-" => Err("Offsets from the end need a seekable file, not stdin".to_string()),
+        Offset::End(_) => {
+            let file_size = fs::metadata(filename)
+                .map_err(|e| format!("Cannot get file info: {}", e))?
+                .len();
+            offset.resolve(file_size)
+        }
+    }
+}
+
+/// Integer type decoded by --as
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum IntWidth {
+    U16,
+    U32,
+    U64,
+}
+
+impl IntWidth {
+    fn bytes(self) -> usize {
+        match self {
+            IntWidth::U16 => 2,
+            IntWidth::U32 => 4,
+            IntWidth::U64 => 8,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            IntWidth::U16 => "u16",
+            IntWidth::U32 => "u32",
+            IntWidth::U64 => "u64",
+        }
+    }
+}
+
+/// Byte order of --as
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Endian {
+    /// Least significant byte first
+    Le,
+    /// Most significant byte first
+    Be,
+}
+
+/// Algorithm used by --checksum
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Checksum {
+    /// CRC-32 (IEEE, as used by zip and PNG)
+    Crc32,
+    /// Sum of all bytes modulo 256
+    Sum8,
+    /// XOR of all bytes
+    Xor,
+}
+
+/// Lookup table for the reflected CRC-32 polynomial 0xEDB88320
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Offset from the start or, for "-16" / "-0x10", from the end of the file
+fn parse_offset(offset_str: &str) -> Result<Offset, String> {
+    match offset_str.strip_prefix('-') {
+        Some(back) => parse_absolute_offset(back).map(Offset::End),
+        None => parse_absolute_offset(offset_str).map(Offset::Start),
+    }
+}
+
+fn parse_absolute_offset(offset_str: &str) -> Result<u64, String> {
     if offset_str.starts_with("0x") || offset_str.starts_with("0X") {
         u64::from_str_radix(&offset_str[2..], 16)
             .map_err(|_| format!("Invalid hex offset: {}", offset_str))
@@ -44,8 +365,12 @@ fn parse_offset(offset_str: &str) -> Result<u64, String> {
 
 fn hex_string_to_bytes(hex_str: &str) -> Result<Vec<u8>, String> {
     let hex_str = hex_str.replace(" ", "");
-    if hex_str.len() % 2 != 0 {
-        return Err("Hex string must have even length".to_string());
+    // Checked first: the pairs below are sliced by byte index
+    if let Some(c) = hex_str.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex digit: {:?}", c));
+    }
+    if !hex_str.len().is_multiple_of(2) {
+        return Err("Hex string must have even length (see --pad-odd)".to_string());
     }
 
     (0..hex_str.len())
@@ -57,73 +382,822 @@ fn hex_string_to_bytes(hex_str: &str) -> Result<Vec<u8>, String> {
         .collect()
 }
 
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+/// Complete an odd-length hex string with a zero nibble on the given side
+fn pad_odd_hex(hex_str: &str, side: PadSide) -> String {
+    let hex_str = hex_str.replace(" ", "");
+    if hex_str.len().is_multiple_of(2) {
+        return hex_str;
+    }
+    match side {
+        PadSide::Left => format!("0{}", hex_str),
+        PadSide::Right => format!("{}0", hex_str),
+    }
+}
+
+/// Parse "OLD:NEW,..." into a 256-entry lookup table (identity by default)
+fn parse_translate_table(spec: &str) -> Result<[u8; 256], String> {
+    let mut table = [0u8; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        *entry = i as u8;
+    }
+
+    for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (old, new) = pair
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid translate pair: {} (expected OLD:NEW)", pair))?;
+        let old = u8::from_str_radix(old.trim(), 16)
+            .map_err(|_| format!("Invalid hex byte: {}", old))?;
+        let new = u8::from_str_radix(new.trim(), 16)
+            .map_err(|_| format!("Invalid hex byte: {}", new))?;
+        table[old as usize] = new;
+    }
+
+    Ok(table)
+}
+
+fn bytes_to_hex(bytes: &[u8], upper: bool) -> String {
+    bytes
+        .iter()
+        .map(|b| if upper { format!("{:02X}", b) } else { format!("{:02x}", b) })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 fn is_printable(b: u8) -> char {
-    if b >= 32 && b < 127 {
+    if (32..127).contains(&b) {
         b as char
     } else {
         '.'
     }
 }
 
-fn read_binary_file(filename: &str, offset: u64, size: Option<usize>) -> Result<(), String> {
+/// Read stdin chunk by chunk, skipping `offset` bytes and keeping at most `size`
+fn read_stdin_region(stdin: &mut impl Read, offset: u64, size: Option<usize>, options: ReadOptions) -> Result<Vec<u8>, String> {
+    if options.chunk_size == 0 {
+        return Err("Chunk size must be greater than 0".to_string());
+    }
+
+    let started = Instant::now();
+    let mut chunk = vec![0u8; options.chunk_size];
+    let mut buffer = vec![];
+    let mut to_skip = offset;
+    let mut total_read = 0u64;
+
+    while size.is_none_or(|s| buffer.len() < s) {
+        let n = stdin.read(&mut chunk)
+            .map_err(|e| format!("Cannot read stdin: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        total_read += n as u64;
+
+        let skipped = to_skip.min(n as u64) as usize;
+        to_skip -= skipped as u64;
+        let wanted = size.map_or(n - skipped, |s| (s - buffer.len()).min(n - skipped));
+        buffer.extend_from_slice(&chunk[skipped..skipped + wanted]);
+        check_load_size(buffer.len() as u64, options.max_size)?;
+    }
+
+    if options.progress {
+        let secs = started.elapsed().as_secs_f64();
+        eprintln!(
+            "Read {} bytes from stdin in {:.3}s ({:.1} MiB/s, {} byte chunks)",
+            total_read,
+            secs,
+            total_read as f64 / (1024.0 * 1024.0) / secs.max(f64::EPSILON),
+            options.chunk_size
+        );
+    }
+
+    Ok(buffer)
+}
+
+/// Bytes of a file (or stdin for "-") from `offset`, along with the absolute start offset
+fn read_region(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions) -> Result<(u64, Vec<u8>), String> {
+    if filename == "-" {
+        let Offset::Start(offset) = offset else {
+            return Err("Offsets from the end need a seekable file, not stdin".to_string());
+        };
+        return read_stdin_region(&mut io::stdin().lock(), offset, size, input).map(|buffer| (offset, buffer));
+    }
+
     let mut file = fs::File::open(filename)
         .map_err(|e| format!("Cannot open file: {}", e))?;
 
-    file.seek(SeekFrom::Start(offset))
-        .map_err(|e| format!("Cannot seek: {}", e))?;
-
     let metadata = fs::metadata(filename)
         .map_err(|e| format!("Cannot get file info: {}", e))?;
     let file_size = metadata.len();
+    let offset = offset.resolve(file_size)?;
+    if offset > file_size {
+        return Err(format!("Offset 0x{:x} is beyond end of file", offset));
+    }
+
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Cannot seek: {}", e))?;
 
     let bytes_to_read = if let Some(s) = size {
         s.min((file_size - offset) as usize)
     } else {
         (file_size - offset) as usize
     };
+    check_load_size(bytes_to_read as u64, input.max_size)?;
 
     let mut buffer = vec![0u8; bytes_to_read];
     file.read_exact(&mut buffer)
         .map_err(|e| format!("Cannot read file: {}", e))?;
 
-    // Display hex dump
-    for (i, chunk) in buffer.chunks(16).enumerate() {
-        let chunk_offset = offset + (i * 16) as u64;
-        let hex_str = bytes_to_hex(chunk);
-        let ascii_str: String = chunk.iter().map(|&b| is_printable(b)).collect();
-        println!("{:08x}: {:47}  |{}|", chunk_offset, hex_str, ascii_str);
+    Ok((offset, buffer))
+}
+
+/// Terminal columns: $COLUMNS when set, otherwise the size of the attached terminal
+fn terminal_columns() -> Option<usize> {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .or_else(|| terminal_size::terminal_size().map(|(w, _)| w.0 as usize))
+}
+
+/// Largest bytes-per-line whose dump line fits in `columns`.
+/// A line is "OOOOOOOO: " (10) + hex (3n - 1) + "  |" (3) + ascii (n) + "|" (1) = 4n + 13.
+fn width_for_columns(columns: Option<usize>) -> usize {
+    match columns {
+        Some(cols) if cols >= 17 => (cols - 13) / 4,
+        Some(_) => 1,
+        None => DEFAULT_WIDTH,
+    }
+}
+
+/// One dump line ("offset: bytes  |ascii|") for at most `layout.width` bytes
+fn dump_row(offset: u64, chunk: &[u8], layout: DumpLayout) -> String {
+    let DumpLayout { width, upper } = layout;
+    let hex_str = bytes_to_hex(chunk, upper);
+    let ascii_str: String = chunk.iter().map(|&b| is_printable(b)).collect();
+    let offset_str = if upper { format!("{:08X}", offset) } else { format!("{:08x}", offset) };
+    format!("{}: {:hex_width$}  |{}|", offset_str, hex_str, ascii_str, hex_width = width * 3 - 1)
+}
+
+/// Hex dump lines ("offset: bytes  |ascii|") for a region starting at `offset`
+fn write_dump(out: &mut impl Write, offset: u64, buffer: &[u8], layout: DumpLayout) -> io::Result<()> {
+    for (i, chunk) in buffer.chunks(layout.width).enumerate() {
+        writeln!(out, "{}", dump_row(offset + (i * layout.width) as u64, chunk, layout))?;
+    }
+    Ok(())
+}
+
+fn read_binary_file(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions, reverse: bool, layout: DumpLayout, out: Option<&str>) -> Result<(), String> {
+    if layout.width == 0 {
+        return Err("Width must be greater than 0".to_string());
+    }
+
+    let (offset, mut buffer) = read_region(filename, offset, size, input)?;
+
+    // Endianness debugging: show the region back to front
+    if reverse {
+        buffer.reverse();
+    }
+
+    // Display hex dump, or save it with --out
+    match out {
+        Some(path) => {
+            let file = fs::File::create(path)
+                .map_err(|e| format!("Cannot create {}: {}", path, e))?;
+            let mut writer = io::BufWriter::new(file);
+            write_dump(&mut writer, offset, &buffer, layout)
+                .and_then(|_| writer.flush())
+                .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+            println!("✓ Dumped {} bytes to {}", buffer.len(), path);
+        }
+        None => write_dump(&mut io::stdout().lock(), offset, &buffer, layout)
+            .map_err(|e| format!("Cannot write dump: {}", e))?,
+    }
+
+    Ok(())
+}
+
+/// Unsigned integer stored in `bytes` (at most 8) with the given byte order
+fn decode_int(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |acc: u64, &b: &u8| (acc << 8) | b as u64;
+    match endian {
+        Endian::Le => bytes.iter().rev().fold(0, fold),
+        Endian::Be => bytes.iter().fold(0, fold),
+    }
+}
+
+fn read_integer(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions, width: IntWidth, endian: Endian) -> Result<(), String> {
+    let wanted = width.bytes();
+    if let Some(s) = size.filter(|&s| s != wanted) {
+        return Err(format!("--as {} reads {} bytes, but --size is {}", width.name(), wanted, s));
+    }
+
+    let (offset, buffer) = read_region(filename, offset, Some(wanted), input)?;
+    if buffer.len() < wanted {
+        return Err(format!("Only {} bytes available at offset 0x{:x}, need {}", buffer.len(), offset, wanted));
+    }
+
+    let value = decode_int(&buffer, endian);
+    println!("{:08x}: {}", offset, bytes_to_hex(&buffer, false));
+    let order = match endian {
+        Endian::Le => "little-endian",
+        Endian::Be => "big-endian",
+    };
+    println!("{} {}: {} (0x{:0digits$x})", width.name(), order, value, value, digits = wanted * 2);
+
+    Ok(())
+}
+
+fn checksum_region(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions, algorithm: Checksum) -> Result<(), String> {
+    let (offset, buffer) = read_region(filename, offset, size, input)?;
+
+    let (name, value) = match algorithm {
+        Checksum::Crc32 => ("CRC32", format!("{:08x}", crc32(&buffer))),
+        Checksum::Sum8 => ("SUM8", format!("{:02x}", buffer.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)))),
+        Checksum::Xor => ("XOR", format!("{:02x}", buffer.iter().fold(0u8, |acc, &b| acc ^ b))),
+    };
+    println!("{} of {} bytes from offset 0x{:x}: 0x{}", name, buffer.len(), offset, value);
+
+    Ok(())
+}
+
+/// (byte, count) for every byte value present in `buffer`
+fn histogram_entries(buffer: &[u8], sort: HistogramSort) -> Vec<(u8, usize)> {
+    let mut counts = [0usize; 256];
+    for &b in buffer {
+        counts[b as usize] += 1;
+    }
+
+    let mut entries: Vec<(u8, usize)> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(b, &count)| (b as u8, count))
+        .collect();
+
+    if let HistogramSort::Count = sort {
+        // Stable sort keeps byte-value order among equal counts
+        entries.sort_by_key(|entry| Reverse(entry.1));
+    }
+
+    entries
+}
+
+fn byte_histogram(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions, sort: HistogramSort) -> Result<(), String> {
+    let (offset, buffer) = read_region(filename, offset, size, input)?;
+    let entries = histogram_entries(&buffer, sort);
+
+    println!("Byte histogram ({} bytes from offset 0x{:x}):", buffer.len(), offset);
+    for (b, count) in entries {
+        println!("{:02x} |{}| {}", b, is_printable(b), count);
+    }
+
+    Ok(())
+}
+
+/// Start offsets of `needle` in `haystack`, optionally allowing overlaps
+fn find_matches(haystack: &[u8], needle: &[u8], overlapping: bool) -> Vec<usize> {
+    let mut matches = vec![];
+    if needle.is_empty() {
+        return matches;
+    }
+
+    let mut i = 0;
+    while i + needle.len() <= haystack.len() {
+        if &haystack[i..i + needle.len()] == needle {
+            matches.push(i);
+            i += if overlapping { 1 } else { needle.len() };
+        } else {
+            i += 1;
+        }
+    }
+
+    matches
+}
+
+/// Call `on_match` with the offset of every (overlapping) occurrence of a
+/// non-empty `needle` in `reader`, whose first byte is at `offset`.
+/// Returns the number of matches.
+fn scan_matches(reader: &mut impl Read, offset: u64, needle: &[u8], chunk_size: usize, mut on_match: impl FnMut(u64)) -> io::Result<usize> {
+    // The last needle.len() - 1 bytes are carried over so matches spanning chunks are found
+    let mut chunk = vec![0u8; chunk_size];
+    let mut window: Vec<u8> = vec![];
+    let mut window_start = offset;
+    let mut found = 0;
+
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        window.extend_from_slice(&chunk[..n]);
+
+        for i in find_matches(&window, needle, true) {
+            on_match(window_start + i as u64);
+            found += 1;
+        }
+
+        let keep = window.len().min(needle.len() - 1);
+        window_start += (window.len() - keep) as u64;
+        window.drain(..window.len() - keep);
+    }
+
+    Ok(found)
+}
+
+/// Report every (overlapping) occurrence of a byte pattern from `offset` on,
+/// scanning chunk by chunk so the file never has to fit in memory
+fn find_in_file(filename: &str, offset: u64, needle: &[u8], chunk_size: usize) -> Result<(), String> {
+    if needle.is_empty() {
+        return Err("Search pattern is empty".to_string());
+    }
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than 0".to_string());
+    }
+
+    let mut reader: Box<dyn Read> = if filename == "-" {
+        let mut stdin = io::stdin().lock();
+        io::copy(&mut (&mut stdin).take(offset), &mut io::sink())
+            .map_err(|e| format!("Cannot read stdin: {}", e))?;
+        Box::new(stdin)
+    } else {
+        let mut file = fs::File::open(filename)
+            .map_err(|e| format!("Cannot open file: {}", e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Cannot seek: {}", e))?;
+        Box::new(file)
+    };
+
+    let found = scan_matches(&mut reader, offset, needle, chunk_size, |at| println!("Found at 0x{:08x}", at))
+        .map_err(|e| format!("Cannot read file: {}", e))?;
+
+    if found == 0 {
+        println!("No matches found");
+    } else {
+        println!("{} match(es)", found);
+    }
+
+    Ok(())
+}
+
+/// Ask a yes/no question on stdin, defaulting to no
+fn confirm(prompt: &str) -> bool {
+    print!("{} [y/N] ", prompt);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+#[derive(Clone, Copy)]
+enum ReplaceScope {
+    /// Only the first match
+    First,
+    /// Every match, after confirmation unless `confirmed`
+    All { confirmed: bool },
+    /// Neither flag: allowed only when there is a single match
+    Single,
+}
+
+fn replace_in_file(filename: &str, offset: u64, spec: &str, scope: ReplaceScope, max_size: u64) -> Result<(), String> {
+    let (old, new) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid replace spec: {} (expected OLD:NEW)", spec))?;
+    let old = hex_string_to_bytes(old)?;
+    let new = hex_string_to_bytes(new)?;
+    if old.is_empty() {
+        return Err("Pattern to replace is empty".to_string());
+    }
+
+    let data = read_whole_file(filename, max_size)?;
+    let start = (offset as usize).min(data.len());
+    let mut matches: Vec<usize> = find_matches(&data[start..], &old, false)
+        .into_iter()
+        .map(|m| m + start)
+        .collect();
+
+    if matches.is_empty() {
+        println!("No matches found");
+        return Ok(());
+    }
+
+    match scope {
+        ReplaceScope::First => matches.truncate(1),
+        ReplaceScope::Single if matches.len() > 1 => {
+            return Err(format!(
+                "{} matches found; use --first-only or --all to replace every occurrence",
+                matches.len()
+            ));
+        }
+        ReplaceScope::All { confirmed: false } if !confirm(&format!("Replace {} occurrences?", matches.len())) => {
+            println!("Aborted");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Rebuild the file so replacements of a different length shift the tail
+    let mut result = Vec::with_capacity(data.len());
+    let mut last = 0;
+    for &m in &matches {
+        result.extend_from_slice(&data[last..m]);
+        result.extend_from_slice(&new);
+        last = m + old.len();
+    }
+    result.extend_from_slice(&data[last..]);
+
+    fs::write(filename, &result).map_err(|e| format!("Cannot write: {}", e))?;
+    for m in &matches {
+        println!("Replaced at 0x{:08x}", m);
+    }
+    println!("✓ {} occurrences replaced", matches.len());
+
+    Ok(())
+}
+
+/// Extend inclusive (first, last) runs with the next differing offset (ascending)
+fn push_offset(ranges: &mut Vec<(u64, u64)>, offset: u64) {
+    match ranges.last_mut() {
+        Some((_, last)) if *last + 1 == offset => *last = offset,
+        _ => ranges.push((offset, offset)),
+    }
+}
+
+/// `^^` under each hex cell where the two rows differ, bytes past the end of
+/// the shorter file included; None when the rows are identical
+fn diff_markers(left: &[u8], right: &[u8], width: usize) -> Option<String> {
+    let marks: Vec<&str> = (0..width)
+        .map(|i| if left.get(i) != right.get(i) { "^^" } else { "  " })
+        .collect();
+    marks.contains(&"^^").then(|| marks.join(" "))
+}
+
+/// Both inputs dumped in parallel columns with the --read row format, rows
+/// aligned by offset; differing bytes are marked under both columns
+fn side_by_side_diff(a: &mut impl Read, b: &mut impl Read, layout: DumpLayout, out: &mut impl Write) -> io::Result<()> {
+    let width = layout.width;
+    // Same line length as width_for_columns assumes
+    let row_width = 4 * width + 13;
+    let mut left = vec![0u8; width];
+    let mut right = vec![0u8; width];
+    let mut start = 0u64;
+    let mut differing = 0;
+
+    loop {
+        let nl = fill_chunk(a, &mut left)?;
+        let nr = fill_chunk(b, &mut right)?;
+        if nl == 0 && nr == 0 {
+            break;
+        }
+        let (left, right) = (&left[..nl], &right[..nr]);
+        differing += (0..nl.max(nr)).filter(|&i| left.get(i) != right.get(i)).count();
+
+        let row = |chunk: &[u8]| if chunk.is_empty() { String::new() } else { dump_row(start, chunk, layout) };
+        writeln!(out, "{:row_width$} | {}", row(left), row(right))?;
+        if let Some(marks) = diff_markers(left, right, width) {
+            let under = format!("{:10}{}", "", marks);
+            writeln!(out, "{:row_width$} | {}", under, under.trim_end())?;
+        }
+        start += width as u64;
+    }
+
+    writeln!(out, "{} differing bytes (^^ marks them in both columns)", differing)
+}
+
+/// Output format of --diff and --diff-hex
+#[derive(Clone, Copy)]
+enum DiffFormat {
+    /// One line per differing byte: "offset: a != b" for --diff,
+    /// "offset: old -> new" for --diff-hex so --patch can replay it
+    Script,
+    /// Contiguous runs of differing offsets
+    Grouped,
+    /// Hex dumps of both files in parallel columns
+    SideBySide { layout: DumpLayout },
+}
+
+/// Read until `buf` is full or EOF, returning the number of bytes read
+fn fill_chunk(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Walk both streams chunk by chunk, calling `on_diff(offset, a, b)` for every
+/// differing byte of their common part. Returns the full length of each stream.
+fn for_each_diff(
+    a: &mut impl Read,
+    b: &mut impl Read,
+    chunk_size: usize,
+    mut on_diff: impl FnMut(u64, u8, u8),
+) -> io::Result<(u64, u64)> {
+    let mut chunk_a = vec![0u8; chunk_size];
+    let mut chunk_b = vec![0u8; chunk_size];
+    let mut offset = 0u64;
+
+    let (na, nb) = loop {
+        let na = fill_chunk(a, &mut chunk_a)?;
+        let nb = fill_chunk(b, &mut chunk_b)?;
+        let common = na.min(nb);
+
+        for (i, (&x, &y)) in chunk_a[..common].iter().zip(&chunk_b[..common]).enumerate() {
+            if x != y {
+                on_diff(offset + i as u64, x, y);
+            }
+        }
+        offset += common as u64;
+
+        // A short chunk means EOF on that side
+        if na < chunk_size || nb < chunk_size {
+            break (na - common, nb - common);
+        }
+    };
+
+    let len_a = offset + na as u64 + io::copy(a, &mut io::sink())?;
+    let len_b = offset + nb as u64 + io::copy(b, &mut io::sink())?;
+    Ok((len_a, len_b))
+}
+
+/// Byte-by-byte comparison streamed in chunks, so neither file is loaded whole.
+/// --diff-hex (`edit_script`) only accepts files of the same length, since its
+/// output is meant for --patch; --diff also reports a length mismatch.
+fn compare_files(filename: &str, other: &str, format: DiffFormat, chunk_size: usize, edit_script: bool) -> Result<(), String> {
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than 0".to_string());
+    }
+
+    let open = |name: &str| fs::File::open(name).map_err(|e| format!("Cannot open {}: {}", name, e));
+    let mut a = open(filename)?;
+    let mut b = open(other)?;
+    let read_error = |e: io::Error| format!("Cannot read file: {}", e);
+
+    if let DiffFormat::SideBySide { layout } = format {
+        if layout.width == 0 {
+            return Err("Width must be greater than 0".to_string());
+        }
+        return side_by_side_diff(&mut a, &mut b, layout, &mut io::stdout().lock()).map_err(read_error);
+    }
+
+    if edit_script {
+        let len = |file: &fs::File| file.metadata().map(|m| m.len()).map_err(read_error);
+        let (len_a, len_b) = (len(&a)?, len(&b)?);
+        if len_a != len_b {
+            return Err(format!(
+                "Edit script needs files of the same length ({} vs {} bytes)",
+                len_a, len_b
+            ));
+        }
+    }
+
+    let mut differing = 0u64;
+    let mut ranges = Vec::new();
+    let (len_a, len_b) = for_each_diff(&mut a, &mut b, chunk_size, |offset, x, y| {
+        differing += 1;
+        match format {
+            DiffFormat::Grouped => push_offset(&mut ranges, offset),
+            _ if edit_script => println!("{}", edit_script_line(offset, x, y)),
+            _ => println!("{}", diff_line(offset, x, y)),
+        }
+    })
+    .map_err(read_error)?;
+
+    if let DiffFormat::Grouped = format {
+        for &(first, last) in &ranges {
+            println!("0x{:08x}-0x{:08x} differ ({} bytes)", first, last, last - first + 1);
+        }
+    }
+
+    let common = len_a.min(len_b);
+    if len_a != len_b {
+        let (longer, len) = if len_a > len_b { (filename, len_a) } else { (other, len_b) };
+        println!("Length mismatch: {} is {} bytes, {} is {} bytes", filename, len_a, other, len_b);
+        println!("0x{:08x}-0x{:08x} only in {}", common, len - 1, longer);
+    }
+
+    if let DiffFormat::Grouped = format {
+        println!("{} bytes changed in {} ranges", differing, ranges.len());
+    } else if !edit_script {
+        if differing == 0 && len_a == len_b {
+            println!("Files are identical");
+        } else {
+            println!("{} differing bytes in the common {} bytes", differing, common);
+        }
     }
 
     Ok(())
 }
 
-fn write_binary_file(filename: &str, offset: u64, hex_data: &str) -> Result<(), String> {
-    let bytes = hex_string_to_bytes(hex_data)?;
+/// One "OFFSET: A != B" --diff line
+fn diff_line(offset: u64, a: u8, b: u8) -> String {
+    format!("0x{:08x}: {:02x} != {:02x}", offset, a, b)
+}
+
+/// One "OFFSET: OLD -> NEW" edit script line, as read back by --patch
+fn edit_script_line(offset: u64, old: u8, new: u8) -> String {
+    format!("0x{:08x}: {:02x} -> {:02x}", offset, old, new)
+}
+
+/// Parse one "OFFSET: OLD -> NEW" edit script line
+fn parse_patch_line(line: &str) -> Result<(u64, Vec<u8>, Vec<u8>), String> {
+    let (offset, change) = line
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid patch line: {}", line))?;
+    let (old, new) = change
+        .split_once("->")
+        .ok_or_else(|| format!("Invalid patch line: {}", line))?;
+
+    let offset = parse_absolute_offset(offset.trim())?;
+    let old = hex_string_to_bytes(old.trim())?;
+    let new = hex_string_to_bytes(new.trim())?;
+    if old.len() != new.len() {
+        return Err(format!("Patch at 0x{:x} changes the length", offset));
+    }
+
+    Ok((offset, old, new))
+}
+
+fn apply_patch_script(filename: &str, script: &str, max_size: u64) -> Result<(), String> {
+    let script = fs::read_to_string(script).map_err(|e| format!("Cannot read patch: {}", e))?;
+    let mut data = read_whole_file(filename, max_size)?;
+
+    let entries = script
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(parse_patch_line)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Check every entry before touching the file
+    for (offset, old, _) in &entries {
+        let current = usize::try_from(*offset)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(old.len())?))
+            .and_then(|range| data.get(range))
+            .ok_or_else(|| format!("Patch at 0x{:x} is beyond end of file", offset))?;
+        if current != old.as_slice() {
+            return Err(format!(
+                "Patch mismatch at 0x{:x}: expected {}, found {}",
+                offset,
+                bytes_to_hex(old, false),
+                bytes_to_hex(current, false)
+            ));
+        }
+    }
+
+    for (offset, _, new) in &entries {
+        let start = *offset as usize;
+        data[start..start + new.len()].copy_from_slice(new);
+    }
+
+    fs::write(filename, &data).map_err(|e| format!("Cannot write: {}", e))?;
+    println!("✓ Applied {} patch entries", entries.len());
+
+    Ok(())
+}
+
+/// The strings --limit lets through, in file order
+fn first_strings(found: &[(usize, String)], limit: Option<usize>) -> &[(usize, String)] {
+    &found[..limit.map_or(found.len(), |l| l.min(found.len()))]
+}
+
+/// Runs of at least MIN_STRING_LEN printable ASCII bytes, with their offsets
+fn find_strings(buffer: &[u8]) -> Vec<(usize, String)> {
+    let mut found = vec![];
+    let mut start = None;
+
+    for (i, &b) in buffer.iter().chain(std::iter::once(&0)).enumerate() {
+        let printable = (32..127).contains(&b);
+        match (printable, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if i - s >= MIN_STRING_LEN {
+                    found.push((s, String::from_utf8_lossy(&buffer[s..i]).into_owned()));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
+
+fn extract_strings(filename: &str, offset: Offset, size: Option<usize>, input: ReadOptions, limit: Option<usize>) -> Result<(), String> {
+    let (offset, buffer) = read_region(filename, offset, size, input)?;
+    let found = find_strings(&buffer);
+    let shown = first_strings(&found, limit);
+
+    for (pos, string) in shown {
+        println!("{:08x}: {}", offset + *pos as u64, string);
+    }
+    println!("Found {} strings, showing {}", found.len(), shown.len());
+
+    Ok(())
+}
+
+fn write_binary_file(filename: &str, offset: Offset, mut bytes: Vec<u8>, reverse: bool, mode: WriteMode) -> Result<(), String> {
+    if reverse {
+        bytes.reverse();
+    }
 
     let mut file = fs::OpenOptions::new()
         .read(true)
         .write(true)
+        .create(true)
+        .truncate(false)
         .open(filename)
         .map_err(|e| format!("Cannot open file: {}", e))?;
 
-    file.seek(SeekFrom::Start(offset))
-        .map_err(|e| format!("Cannot seek: {}", e))?;
+    // Past EOF, zero-fill the gap explicitly rather than relying on a sparse seek
+    let file_size = file.metadata()
+        .map_err(|e| format!("Cannot get file info: {}", e))?
+        .len();
+    if !matches!(mode, WriteMode::Append) {
+        let start = offset.resolve(file_size)?;
+        if start > file_size {
+            file.set_len(start)
+                .map_err(|e| format!("Cannot extend file: {}", e))?;
+        }
+    }
+
+    let offset = match mode {
+        WriteMode::Overwrite | WriteMode::Insert => file.seek(offset.seek_from()),
+        WriteMode::Append => file.seek(SeekFrom::End(0)),
+    }
+    .map_err(|e| format!("Cannot seek: {}", e))?;
+
+    // Insert: keep everything from the offset, then write it back after the new bytes
+    let mut tail = Vec::new();
+    if let WriteMode::Insert = mode {
+        file.read_to_end(&mut tail)
+            .map_err(|e| format!("Cannot read file: {}", e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Cannot seek: {}", e))?;
+    }
 
     file.write_all(&bytes)
+        .and_then(|_| file.write_all(&tail))
         .map_err(|e| format!("Cannot write: {}", e))?;
 
     println!("Writing {} bytes at offset 0x{:x}", bytes.len(), offset);
-    println!("Hex: {}", bytes_to_hex(&bytes));
+    println!("Hex: {}", bytes_to_hex(&bytes, false));
     println!("ASCII: {}", bytes.iter().map(|&b| is_printable(b)).collect::<String>());
+    if !matches!(mode, WriteMode::Overwrite) || offset + bytes.len() as u64 > file_size {
+        let new_len = file.metadata()
+            .map_err(|e| format!("Cannot get file info: {}", e))?
+            .len();
+        println!("New file length: {} bytes (0x{:x})", new_len, new_len);
+    }
     println!("✓ Successfully written");
 
     Ok(())
 }
 
+fn translate_binary_file(filename: &str, offset: u64, size: Option<usize>, table: &[u8; 256], max_size: u64) -> Result<(), String> {
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(filename)
+        .map_err(|e| format!("Cannot open file: {}", e))?;
+
+    let file_size = file.metadata()
+        .map_err(|e| format!("Cannot get file info: {}", e))?
+        .len();
+    if offset > file_size {
+        return Err(format!("Offset 0x{:x} is beyond end of file", offset));
+    }
+
+    let available = (file_size - offset) as usize;
+    let bytes_to_read = size.map_or(available, |s| s.min(available));
+    check_load_size(bytes_to_read as u64, max_size)?;
+
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Cannot seek: {}", e))?;
+    let mut buffer = vec![0u8; bytes_to_read];
+    file.read_exact(&mut buffer)
+        .map_err(|e| format!("Cannot read file: {}", e))?;
+
+    let mut changed = 0;
+    for b in buffer.iter_mut() {
+        let translated = table[*b as usize];
+        if translated != *b {
+            *b = translated;
+            changed += 1;
+        }
+    }
+
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Cannot seek: {}", e))?;
+    file.write_all(&buffer)
+        .map_err(|e| format!("Cannot write: {}", e))?;
+
+    println!("Translating {} bytes at offset 0x{:x}", buffer.len(), offset);
+    println!("✓ {} bytes changed", changed);
+
+    Ok(())
+}
+
 fn main() {
     let args = Args::parse();
 
@@ -133,11 +1207,43 @@ fn main() {
         println!("Read & Write binary files in hexadecimal");
         println!();
         println!("Options:");
-        println!("  -f, --file <FILE>       Target file");
+        println!("  -f, --file <FILE>       Target file (- reads stdin)");
         println!("  -r, --read              Read mode (display hex)");
         println!("  -w, --write <HEX>       Write mode (hex string to write)");
-        println!("  -o, --offset <OFFSET>   Offset in bytes (decimal or 0x...)");
+        println!("  --write-from <PATH>     Write the raw bytes of another file");
+        println!("  --append                Write at end of file (ignores --offset)");
+        println!("  --insert                Insert at --offset, shifting the following bytes");
+        println!("  --pad-odd [left|right]  Pad odd-length hex with a 0 nibble [default: left]");
+        println!("  -o, --offset <OFFSET>   Offset in bytes (decimal or 0x..., -N counts from EOF), alias --offset-start");
         println!("  -s, --size <SIZE>       Number of bytes to read");
+        println!("  --out <FILE>            Save the hex dump to FILE instead of printing it");
+        println!("  --chunk-size <N>        Stdin read buffer size [default: 65536]");
+        println!("  --progress              Report stdin read throughput");
+        println!("  --width <N>             Bytes per dump line [default: 16]");
+        println!("  --auto-width            Fit bytes per line to the terminal width");
+        println!("  --upper                 Uppercase hex digits in the dump");
+        println!("  --max-file-size <N>     Max bytes loaded into memory [default: 268435456]");
+        println!("  --reverse-bytes         Reverse byte order of the region read or written");
+        println!("  --translate <TABLE>     Replace bytes in place (OLD:NEW,... e.g. 00:20,FF:00)");
+        println!("  --as <u16|u32|u64>      Decode the range as an integer instead of dumping it");
+        println!("  --endian <le|be>        Byte order for --as [default: le]");
+        println!("  --checksum <ALGO>       Checksum of the range (crc32, sum8, xor)");
+        println!("  --histogram             Count occurrences of each byte value");
+        println!("  --sort <value|count>    Histogram order [default: value]");
+        println!("  --strings               List printable strings (at least 4 chars)");
+        println!("  --limit <N>             Show at most N strings");
+        println!("  --replace <OLD:NEW>     Replace hex pattern OLD with NEW");
+        println!("  --first-only            Replace only the first occurrence");
+        println!("  --all                   Replace every occurrence (asks for confirmation)");
+        println!("  -y, --yes               Don't ask for confirmation");
+        println!("  --find <HEX>            List every offset where the hex pattern occurs");
+        println!("  --find-str <TEXT>       Same as --find for a string, matched on its UTF-8 bytes");
+        println!("                          (non-ASCII text only matches UTF-8 encoded data)");
+        println!("  --diff <OTHER>          List offsets where FILE and OTHER differ (streamed)");
+        println!("  --diff-hex <OTHER>      Print an edit script turning FILE into OTHER");
+        println!("  --group-diffs           With --diff/--diff-hex, report contiguous ranges instead of bytes");
+        println!("  --side-by-side          With --diff/--diff-hex, dump both files in parallel columns");
+        println!("  --patch <SCRIPT>        Apply an edit script from --diff-hex");
         println!("  -h, --help              Print help");
         return;
     }
@@ -150,15 +1256,488 @@ fn main() {
         }
     };
 
-    if let Some(hex_data) = args.write {
-        if let Err(e) = write_binary_file(&args.file, offset, &hex_data) {
+    let input = ReadOptions {
+        chunk_size: args.chunk_size,
+        progress: args.progress,
+        max_size: args.max_file_size,
+    };
+
+    let width = if args.auto_width {
+        width_for_columns(terminal_columns())
+    } else {
+        args.width
+    };
+
+    if args.write.is_some() || args.write_from.is_some() {
+        let data = match (args.write, &args.write_from) {
+            (Some(hex_data), _) => {
+                let hex_data = match args.pad_odd {
+                    Some(side) => pad_odd_hex(&hex_data, side),
+                    None => hex_data,
+                };
+                hex_string_to_bytes(&hex_data)
+            }
+            (None, Some(source)) => read_whole_file(source, args.max_file_size),
+            (None, None) => unreachable!(),
+        };
+        let mode = if args.append {
+            WriteMode::Append
+        } else if args.insert {
+            WriteMode::Insert
+        } else {
+            WriteMode::Overwrite
+        };
+        let result = data.and_then(|bytes| write_binary_file(&args.file, offset, bytes, args.reverse_bytes, mode));
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(spec) = args.translate {
+        let result = parse_translate_table(&spec)
+            .and_then(|table| {
+                let offset = absolute_offset(&args.file, offset)?;
+                translate_binary_file(&args.file, offset, args.size, &table, args.max_file_size)
+            });
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(spec) = args.replace {
+        let scope = if args.first_only {
+            ReplaceScope::First
+        } else if args.all {
+            ReplaceScope::All { confirmed: args.yes }
+        } else {
+            ReplaceScope::Single
+        };
+        let result = absolute_offset(&args.file, offset)
+            .and_then(|offset| replace_in_file(&args.file, offset, &spec, scope, args.max_file_size));
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+    } else if args.find.is_some() || args.find_str.is_some() {
+        // Text is matched on its UTF-8 encoding
+        let needle = match (&args.find, &args.find_str) {
+            (Some(hex), _) => hex_string_to_bytes(hex),
+            (None, Some(text)) => Ok(text.as_bytes().to_vec()),
+            (None, None) => unreachable!(),
+        };
+        let result = needle.and_then(|needle| {
+            let offset = absolute_offset(&args.file, offset)?;
+            find_in_file(&args.file, offset, &needle, args.chunk_size)
+        });
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some((other, edit_script)) = args.diff.map(|o| (o, false)).or(args.diff_hex.map(|o| (o, true))) {
+        let diff_format = if args.side_by_side {
+            DiffFormat::SideBySide { layout: DumpLayout { width, upper: args.upper } }
+        } else if args.group_diffs {
+            DiffFormat::Grouped
+        } else {
+            DiffFormat::Script
+        };
+        if let Err(e) = compare_files(&args.file, &other, diff_format, args.chunk_size, edit_script) {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(script) = args.patch {
+        if let Err(e) = apply_patch_script(&args.file, &script, args.max_file_size) {
+            eprintln!("Error: {}", e);
+        }
+    } else if args.strings {
+        if let Err(e) = extract_strings(&args.file, offset, args.size, input, args.limit) {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(int_width) = args.as_int {
+        if let Err(e) = read_integer(&args.file, offset, args.size, input, int_width, args.endian) {
+            eprintln!("Error: {}", e);
+        }
+    } else if let Some(algorithm) = args.checksum {
+        if let Err(e) = checksum_region(&args.file, offset, args.size, input, algorithm) {
+            eprintln!("Error: {}", e);
+        }
+    } else if args.histogram {
+        if let Err(e) = byte_histogram(&args.file, offset, args.size, input, args.sort) {
             eprintln!("Error: {}", e);
         }
     } else if args.read {
-        if let Err(e) = read_binary_file(&args.file, offset, args.size) {
+        let layout = DumpLayout { width, upper: args.upper };
+        if let Err(e) = read_binary_file(&args.file, offset, args.size, input, args.reverse_bytes, layout, args.out.as_deref()) {
             eprintln!("Error: {}", e);
         }
     } else {
         eprintln!("Please specify --read or --write mode");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hextool_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn translate_rewrites_only_mapped_bytes() {
+        let table = parse_translate_table("00:ff, 41:61").unwrap();
+        assert_eq!((table[0x00], table[0x41], table[0x42]), (0xff, 0x61, 0x42));
+        assert!(parse_translate_table("41").is_err());
+        assert!(parse_translate_table("41:zz").is_err());
+
+        let path = temp_path("translate.bin");
+        fs::write(&path, b"\x00AB\x00A").unwrap();
+        translate_binary_file(path.to_str().unwrap(), 1, Some(3), &table, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(data, b"\x00aB\xffA");
+    }
+
+    #[test]
+    fn histogram_sorts_by_value_or_count() {
+        let buffer = b"abcbcc\x00";
+        assert_eq!(
+            histogram_entries(buffer, HistogramSort::Value),
+            [(0x00, 1), (b'a', 1), (b'b', 2), (b'c', 3)]
+        );
+        // Equal counts stay in byte order
+        assert_eq!(
+            histogram_entries(buffer, HistogramSort::Count),
+            [(b'c', 3), (b'b', 2), (0x00, 1), (b'a', 1)]
+        );
+        assert!(histogram_entries(b"", HistogramSort::Count).is_empty());
+    }
+
+    #[test]
+    fn edit_script_round_trips_through_patch() {
+        let (old, new) = (b"hello world".to_vec(), b"jello wOrld".to_vec());
+        let mut script = String::new();
+        for_each_diff(&mut old.as_slice(), &mut new.as_slice(), 4, |offset, x, y| {
+            script.push_str(&edit_script_line(offset, x, y));
+            script.push('\n');
+        })
+        .unwrap();
+        assert_eq!(script, "0x00000000: 68 -> 6a\n0x00000007: 6f -> 4f\n");
+        assert_eq!(parse_patch_line("0x00000007: 6f -> 4f"), Ok((7, vec![0x6f], vec![0x4f])));
+
+        let (target, patch) = (temp_path("patch_target.bin"), temp_path("patch.txt"));
+        fs::write(&target, &old).unwrap();
+        fs::write(&patch, &script).unwrap();
+        let applied = apply_patch_script(target.to_str().unwrap(), patch.to_str().unwrap(), DEFAULT_MAX_FILE_SIZE);
+        let data = fs::read(&target).unwrap();
+        fs::remove_file(&target).unwrap();
+        fs::remove_file(&patch).unwrap();
+        assert_eq!((applied, data), (Ok(()), new));
+
+        // An offset past the addressable range is refused, not wrapped
+        fs::write(&target, &old).unwrap();
+        fs::write(&patch, "0xffffffffffffffff: 68 -> 6a\n").unwrap();
+        let applied = apply_patch_script(target.to_str().unwrap(), patch.to_str().unwrap(), DEFAULT_MAX_FILE_SIZE);
+        let data = fs::read(&target).unwrap();
+        fs::remove_file(&target).unwrap();
+        fs::remove_file(&patch).unwrap();
+        assert_eq!(applied, Err("Patch at 0xffffffffffffffff is beyond end of file".to_string()));
+        assert_eq!(data, old);
+    }
+
+    #[test]
+    fn strings_are_printable_runs_of_minimum_length() {
+        let found = find_strings(b"\x00abc\x01ELF file\xffend!");
+        assert_eq!(found, [(5, "ELF file".to_string()), (14, "end!".to_string())]);
+        assert!(find_strings(b"a\x00bc\x00def").is_empty());
+
+        let found = find_strings(b"first\x00second\x00third\x00fourth");
+        assert_eq!(found.len(), 4);
+        assert_eq!(first_strings(&found, Some(2)), [(0, "first".to_string()), (6, "second".to_string())]);
+        assert_eq!(first_strings(&found, Some(9)).len(), 4);
+        assert_eq!(first_strings(&found, None).len(), 4);
+    }
+
+    #[test]
+    fn reverse_bytes_flips_written_and_read_regions() {
+        let path = temp_path("reverse.bin");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, [0u8; 4]).unwrap();
+        write_binary_file(filename, Offset::Start(0), vec![0x12, 0x34, 0x56, 0x78], true, WriteMode::Overwrite).unwrap();
+        let written = fs::read(&path).unwrap();
+
+        let input = ReadOptions { chunk_size: DEFAULT_CHUNK_SIZE, progress: false, max_size: DEFAULT_MAX_FILE_SIZE };
+        let out = temp_path("reverse_dump.txt");
+        read_binary_file(filename, Offset::Start(1), Some(2), input, true, DumpLayout { width: 16, upper: false }, out.to_str()).unwrap();
+        let dump = fs::read_to_string(&out).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&out).unwrap();
+
+        assert_eq!(written, [0x78, 0x56, 0x34, 0x12]);
+        assert!(dump.starts_with("00000001: 34 56 "));
+    }
+
+    #[test]
+    fn chunked_stdin_reads_match_for_any_chunk_size() {
+        let data: Vec<u8> = (0..=255).collect();
+        for chunk_size in [1, 7, 64, 1024] {
+            let options = ReadOptions { chunk_size, progress: false, max_size: DEFAULT_MAX_FILE_SIZE };
+            let region = read_stdin_region(&mut data.as_slice(), 10, Some(20), options).unwrap();
+            assert_eq!(region, &data[10..30], "chunk size {}", chunk_size);
+            let rest = read_stdin_region(&mut data.as_slice(), 250, None, options).unwrap();
+            assert_eq!(rest, &data[250..]);
+        }
+
+        let options = ReadOptions { chunk_size: 0, progress: false, max_size: DEFAULT_MAX_FILE_SIZE };
+        assert!(read_stdin_region(&mut data.as_slice(), 0, None, options).is_err());
+        let options = ReadOptions { chunk_size: 16, progress: false, max_size: 100 };
+        assert!(read_stdin_region(&mut data.as_slice(), 0, None, options).is_err());
+    }
+
+    #[test]
+    fn auto_width_lines_fit_the_columns() {
+        assert_eq!(width_for_columns(Some(80)), 16);
+        assert_eq!(width_for_columns(Some(200)), 46);
+        assert_eq!(width_for_columns(Some(5)), 1);
+        assert_eq!(width_for_columns(None), DEFAULT_WIDTH);
+
+        for columns in [17, 80, 133] {
+            let width = width_for_columns(Some(columns));
+            let mut out = Vec::new();
+            write_dump(&mut out, 0, &[b'x'; 300], DumpLayout { width, upper: false }).unwrap();
+            let longest = String::from_utf8(out).unwrap().lines().map(str::len).max().unwrap();
+            assert!(longest <= columns && longest + 4 > columns, "{} columns", columns);
+        }
+    }
+
+    #[test]
+    fn replace_needs_a_scope_for_several_matches() {
+        let path = temp_path("replace.bin");
+        let filename = path.to_str().unwrap();
+        let replace = |scope| {
+            fs::write(&path, b"abXabXab").unwrap();
+            let result = replace_in_file(filename, 1, "6162:41", scope, DEFAULT_MAX_FILE_SIZE);
+            (result, fs::read(&path).unwrap())
+        };
+
+        let (result, data) = replace(ReplaceScope::Single);
+        assert_eq!(result.unwrap_err(), "2 matches found; use --first-only or --all to replace every occurrence");
+        assert_eq!(data, b"abXabXab");
+        assert_eq!(replace(ReplaceScope::First), (Ok(()), b"abXAXab".to_vec()));
+        assert_eq!(replace(ReplaceScope::All { confirmed: true }), (Ok(()), b"abXAXA".to_vec()));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn loads_over_the_size_limit_are_refused() {
+        assert!(check_load_size(10, 10).is_ok());
+        assert!(check_load_size(11, 10).unwrap_err().starts_with("Refusing to load 11 bytes (limit 10)"));
+
+        let path = temp_path("limit.bin");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, [0u8; 32]).unwrap();
+        let whole = read_whole_file(filename, 16);
+        let input = ReadOptions { chunk_size: DEFAULT_CHUNK_SIZE, progress: false, max_size: 16 };
+        let region = read_region(filename, Offset::Start(8), Some(16), input);
+        let too_big = read_region(filename, Offset::Start(8), None, input);
+        fs::remove_file(&path).unwrap();
+
+        assert!(whole.is_err() && too_big.is_err());
+        assert_eq!(region, Ok((8, vec![0; 16])));
+    }
+
+    #[test]
+    fn append_ignores_the_offset() {
+        let path = temp_path("append.bin");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, b"abc").unwrap();
+        write_binary_file(filename, Offset::Start(0), b"de".to_vec(), false, WriteMode::Append).unwrap();
+        write_binary_file(filename, Offset::Start(100), b"f".to_vec(), false, WriteMode::Append).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(data, b"abcdef");
+    }
+
+    #[test]
+    fn odd_hex_is_padded_and_bad_digits_rejected() {
+        assert_eq!(pad_odd_hex("a bc", PadSide::Left), "0abc");
+        assert_eq!(pad_odd_hex("abc", PadSide::Right), "abc0");
+        assert_eq!(pad_odd_hex("ab cd", PadSide::Left), "abcd");
+        assert_eq!(hex_string_to_bytes(&pad_odd_hex("abc", PadSide::Left)), Ok(vec![0x0a, 0xbc]));
+
+        assert_eq!(hex_string_to_bytes("abc").unwrap_err(), "Hex string must have even length (see --pad-odd)");
+        assert_eq!(hex_string_to_bytes("zz").unwrap_err(), "Invalid hex digit: 'z'");
+        // Multi-byte characters must not be sliced into
+        assert_eq!(hex_string_to_bytes("aé").unwrap_err(), "Invalid hex digit: 'é'");
+    }
+
+    #[test]
+    fn differing_offsets_group_into_runs() {
+        let mut ranges = Vec::new();
+        for offset in [0, 1, 2, 5, 7, 8] {
+            push_offset(&mut ranges, offset);
+        }
+        assert_eq!(ranges, [(0, 2), (5, 5), (7, 8)]);
+    }
+
+    #[test]
+    fn side_by_side_marks_differences_and_blanks_missing_bytes() {
+        let side_by_side = |a: &[u8], b: &[u8]| {
+            let mut out = Vec::new();
+            side_by_side_diff(&mut &a[..], &mut &b[..], DumpLayout { width: 4, upper: false }, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let same = side_by_side(b"abcdef", b"abcdef");
+        assert_eq!(
+            same,
+            "00000000: 61 62 63 64  |abcd| | 00000000: 61 62 63 64  |abcd|\n\
+             00000004: 65 66        |ef|   | 00000004: 65 66        |ef|\n\
+             0 differing bytes (^^ marks them in both columns)\n"
+        );
+
+        let lines: Vec<String> = side_by_side(b"\x01\x02\x03", b"\x01\xff\x03").lines().map(String::from).collect();
+        assert_eq!(lines[0], "00000000: 01 02 03     |...|  | 00000000: 01 ff 03     |...|");
+        assert_eq!(lines[1], format!("{:13}^^{:14} | {:13}^^", "", "", ""));
+        assert_eq!(lines[2], "1 differing bytes (^^ marks them in both columns)");
+
+        assert_eq!(diff_markers(b"\x01\x02\x03", b"\x01", 4).as_deref(), Some("   ^^ ^^   "));
+    }
+
+    #[test]
+    fn writes_past_eof_zero_fill_and_create_the_file() {
+        let path = temp_path("beyond.bin");
+        let filename = path.to_str().unwrap();
+        let _ = fs::remove_file(&path);
+        write_binary_file(filename, Offset::Start(2), b"ab".to_vec(), false, WriteMode::Overwrite).unwrap();
+        let created = fs::read(&path).unwrap();
+        write_binary_file(filename, Offset::Start(6), b"c".to_vec(), false, WriteMode::Overwrite).unwrap();
+        let extended = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(created, b"\0\0ab");
+        assert_eq!(extended, b"\0\0ab\0\0c");
+    }
+
+    #[test]
+    fn insert_shifts_the_tail_forward() {
+        let path = temp_path("insert.bin");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, b"abcdef").unwrap();
+        write_binary_file(filename, Offset::Start(2), b"XY".to_vec(), false, WriteMode::Insert).unwrap();
+        let middle = fs::read(&path).unwrap();
+        write_binary_file(filename, Offset::End(0), b"!".to_vec(), false, WriteMode::Insert).unwrap();
+        let end = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(middle, b"abXYcdef");
+        assert_eq!(end, b"abXYcdef!");
+    }
+
+    #[test]
+    fn find_reports_matches_across_chunk_boundaries() {
+        assert_eq!(find_matches(b"aaaa", b"aa", true), [0, 1, 2]);
+        assert_eq!(find_matches(b"aaaa", b"aa", false), [0, 2]);
+        assert!(find_matches(b"abc", b"", true).is_empty());
+
+        let data = b"\xde\xad\xbe\xef..\xde\xad\xbe\xef\xbe\xef";
+        for chunk_size in [1, 3, 5, 64] {
+            let mut found = vec![];
+            let count = scan_matches(&mut data.as_slice(), 0x100, b"\xbe\xef", chunk_size, |at| found.push(at)).unwrap();
+            assert_eq!((count, found), (3, vec![0x102, 0x108, 0x10a]), "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn negative_offsets_count_back_from_the_end() {
+        assert!(matches!(parse_offset("0x10"), Ok(Offset::Start(16))));
+        assert!(matches!(parse_offset("-16"), Ok(Offset::End(16))));
+        assert!(matches!(parse_offset("-0x10"), Ok(Offset::End(16))));
+        assert!(parse_offset("-x").is_err());
+
+        assert_eq!(Offset::End(16).resolve(100), Ok(84));
+        assert_eq!(Offset::End(0).resolve(100), Ok(100));
+        assert_eq!(Offset::Start(200).resolve(100), Ok(200));
+        assert!(Offset::End(101).resolve(100).is_err());
+        assert!(absolute_offset("-", Offset::End(1)).is_err());
+    }
+
+    #[test]
+    fn integers_decode_in_either_byte_order() {
+        let bytes = [0x01, 0x02, 0x03, 0x04];
+        assert_eq!(decode_int(&bytes, Endian::Le), 0x04030201);
+        assert_eq!(decode_int(&bytes, Endian::Be), 0x01020304);
+        assert_eq!(decode_int(&[0xff; 8], Endian::Le), u64::MAX);
+        assert_eq!(decode_int(&[0x34, 0x12], Endian::Le), 0x1234);
+        assert_eq!([IntWidth::U16, IntWidth::U32, IntWidth::U64].map(IntWidth::bytes), [2, 4, 8]);
+    }
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414fa339);
+    }
+
+    #[test]
+    fn write_from_copies_the_source_bytes() {
+        let (source, target) = (temp_path("write_from_source.bin"), temp_path("write_from_target.bin"));
+        fs::write(&source, b"\x00\x01\xff").unwrap();
+        fs::write(&target, b"abcdef").unwrap();
+        let bytes = read_whole_file(source.to_str().unwrap(), DEFAULT_MAX_FILE_SIZE).unwrap();
+        write_binary_file(target.to_str().unwrap(), Offset::Start(1), bytes, false, WriteMode::Overwrite).unwrap();
+        let data = fs::read(&target).unwrap();
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(&target).unwrap();
+
+        assert_eq!(data, b"a\x00\x01\xffef");
+        assert!(read_whole_file(source.to_str().unwrap(), DEFAULT_MAX_FILE_SIZE).is_err());
+    }
+
+    #[test]
+    fn diff_streams_report_offsets_and_full_lengths() {
+        let a: Vec<u8> = (0..100).collect();
+        let mut b = a.clone();
+        b[3] = 0;
+        b[64] = 0;
+        b.extend_from_slice(b"tail");
+
+        for chunk_size in [1, 10, 64, 4096] {
+            let mut diffs = vec![];
+            let lengths = for_each_diff(&mut a.as_slice(), &mut b.as_slice(), chunk_size, |offset, x, y| diffs.push((offset, x, y))).unwrap();
+            assert_eq!(lengths, (100, 104), "chunk size {}", chunk_size);
+            assert_eq!(diffs, [(3, 3, 0), (64, 64, 0)]);
+        }
+
+        assert_eq!(diff_line(0x1a, 0x3f, 0x7e), "0x0000001a: 3f != 7e");
+    }
+
+    #[test]
+    fn dump_goes_to_the_out_file() {
+        let (source, out) = (temp_path("out_source.bin"), temp_path("out_dump.txt"));
+        fs::write(&source, b"Hello, hextool!\n\x00\x01").unwrap();
+        let input = ReadOptions { chunk_size: DEFAULT_CHUNK_SIZE, progress: false, max_size: DEFAULT_MAX_FILE_SIZE };
+        let layout = DumpLayout { width: 16, upper: false };
+        read_binary_file(source.to_str().unwrap(), Offset::Start(0), None, input, false, layout, out.to_str()).unwrap();
+        let dump = fs::read_to_string(&out).unwrap();
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(&out).unwrap();
+
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "00000000: 48 65 6c 6c 6f 2c 20 68 65 78 74 6f 6f 6c 21 0a  |Hello, hextool!.|");
+        assert!(lines[1].starts_with("00000010: 00 01 ") && lines[1].ends_with("  |..|"));
+    }
+
+    #[test]
+    fn upper_switches_bytes_and_offsets_to_uppercase() {
+        assert_eq!(bytes_to_hex(&[0xab, 0x0c], true), "AB 0C");
+        assert_eq!(bytes_to_hex(&[0xab, 0x0c], false), "ab 0c");
+
+        let mut out = Vec::new();
+        write_dump(&mut out, 0xabc, &[0xde, 0xad], DumpLayout { width: 2, upper: true }).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "00000ABC: DE AD  |..|\n");
+    }
+
+    #[test]
+    fn text_search_matches_the_utf8_encoding() {
+        let data = "naïve text, naïve again".as_bytes();
+        let mut found = vec![];
+        scan_matches(&mut &data[..], 0, "ïve".as_bytes(), 4, |at| found.push(at)).unwrap();
+        // Offsets count bytes: ï takes two
+        assert_eq!(found, [2, 15]);
+    }
+}
@@ -1,39 +1,335 @@
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use clap::{Parser, Subcommand};
+use hmac::{Hmac, Mac};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use rand::Rng;
 
+/// Stream cipher chat avec échange de clés Diffie-Hellman
+#[derive(Parser, Debug)]
+#[command(name = "streamchat", version = "1.0", about = "Stream cipher chat with Diffie-Hellman key generation")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Explain the key exchange and show per-message encrypt/decrypt dumps
+    #[arg(long, global = true)]
+    explain: bool,
+
+    /// Send a keepalive ping every SECS seconds; close if the previous one got no pong
+    #[arg(long, global = true, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    keepalive: Option<u64>,
+
+    /// Send the lines of FILE instead of reading stdin, logging each ciphertext
+    #[arg(long, global = true, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// Encrypt stdin to stdout with the keystream seeded by this shared secret (hex), no networking
+    #[arg(long, value_name = "HEX_SECRET", conflicts_with = "decrypt")]
+    encrypt: Option<String>,
+
+    /// Decrypt stdin to stdout with the keystream seeded by this shared secret (hex), no networking
+    #[arg(long, value_name = "HEX_SECRET")]
+    decrypt: Option<String>,
+
+    /// Report file transfer progress, at most once every SECS seconds [default: 1]
+    #[arg(long, global = true, value_name = "SECS", num_args = 0..=1, default_missing_value = "1")]
+    progress: Option<u64>,
+
+    /// Name shown to the peer instead of [SERVER]/[CLIENT]
+    #[arg(long, global = true)]
+    name: Option<String>,
+
+    /// Use the old LCG keystream instead of ChaCha20 (insecure, for talking to old builds)
+    #[arg(long, global = true)]
+    legacy_cipher: bool,
+
+    /// Use the 64-bit toy DH group instead of the 2048-bit one (brute-forceable, for demos)
+    #[arg(long, global = true)]
+    weak_dh: bool,
+
+    /// Read our private key from a file (single hex u64) for reproducible tests
+    #[arg(long, global = true, hide = true)]
+    seed_file: Option<String>,
+}
+
+/// Settings shared by both ends of a chat session
+struct ChatConfig {
+    explain: bool,
+    /// Fixed private key instead of a random one (--seed-file)
+    private_key: Option<u64>,
+    /// Keepalive ping interval (--keepalive)
+    keepalive: Option<Duration>,
+    /// Scripted input lines used instead of stdin (--replay)
+    replay: Option<Vec<String>>,
+    /// LCG keystream instead of ChaCha20 (--legacy-cipher)
+    legacy_cipher: bool,
+    /// Our display name (--name)
+    name: Option<String>,
+    /// Minimum time between file transfer progress lines (--progress)
+    progress: Option<Duration>,
+    /// 64-bit DH group instead of the 2048-bit one (--weak-dh)
+    weak_dh: bool,
+}
+
+// Framing: a 4-byte little-endian length followed by the ciphertext and
+// its HMAC-SHA256 tag (the length covers both).
+// Lengths with the high bit set are control frames carrying no payload.
+const CONTROL_FLAG: u32 = 1 << 31;
+const CONTROL_PING: u32 = CONTROL_FLAG | 1;
+const CONTROL_PONG: u32 = CONTROL_FLAG | 2;
+
+// Every decrypted payload starts with one of these kind bytes
+const MSG_TEXT: u8 = 0;
+/// File header: u64 little-endian size, then the file name
+const MSG_FILE_START: u8 = 1;
+const MSG_FILE_CHUNK: u8 = 2;
+/// End of file: SHA-256 of the whole content
+const MSG_FILE_END: u8 = 3;
+
+/// Files are sent in pieces of this size, never loaded whole
+const FILE_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Where received files are saved
+const DOWNLOAD_DIR: &str = "downloads";
+
+enum Frame {
+    Data(Vec<u8>),
+    Ping,
+    Pong,
+}
+
+fn write_frame(stream: &mut impl Write, frame: &Frame) -> io::Result<()> {
+    match frame {
+        Frame::Data(cipher) => {
+            stream.write_all(&(cipher.len() as u32).to_le_bytes())?;
+            stream.write_all(cipher)
+        }
+        Frame::Ping => stream.write_all(&CONTROL_PING.to_le_bytes()),
+        Frame::Pong => stream.write_all(&CONTROL_PONG.to_le_bytes()),
+    }
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Frame> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+
+    match u32::from_le_bytes(len_bytes) {
+        CONTROL_PING => Ok(Frame::Ping),
+        CONTROL_PONG => Ok(Frame::Pong),
+        len if len & CONTROL_FLAG != 0 => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown control frame {:08x}", len),
+        )),
+        len => {
+            let mut cipher = vec![0u8; len as usize];
+            stream.read_exact(&mut cipher)?;
+            Ok(Frame::Data(cipher))
+        }
+    }
+}
+
+/// Read frames in the background: answer pings, record pongs and hand
+/// ciphertexts to the chat loop. The channel closes when the peer goes away.
+fn spawn_reader(mut reader: TcpStream, writer: Arc<Mutex<TcpStream>>, awaiting_pong: Arc<AtomicBool>) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        while let Ok(frame) = read_frame(&mut reader) {
+            match frame {
+                Frame::Data(cipher) => {
+                    if tx.send(cipher).is_err() {
+                        break;
+                    }
+                }
+                Frame::Ping => {
+                    if write_frame(&mut *writer.lock().unwrap(), &Frame::Pong).is_err() {
+                        break;
+                    }
+                }
+                Frame::Pong => awaiting_pong.store(false, Ordering::SeqCst),
+            }
+        }
+    });
+
+    rx
+}
+
+/// Ping the peer every `interval`; shut the connection down when a ping is
+/// still unanswered at the next tick.
+fn spawn_keepalive(writer: Arc<Mutex<TcpStream>>, awaiting_pong: Arc<AtomicBool>, interval: Duration, label: &'static str) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        if awaiting_pong.swap(true, Ordering::SeqCst) {
+            println!("{} [KEEPALIVE] No pong from peer, closing connection", label);
+            let _ = writer.lock().unwrap().shutdown(Shutdown::Both);
+            break;
+        }
+
+        if write_frame(&mut *writer.lock().unwrap(), &Frame::Ping).is_err() {
+            break;
+        }
+    });
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start server
+    Server {
+        /// Port to listen on [default: 8080]
+        port: Option<String>,
+    },
+    /// Connect to server
+    Client {
+        /// Server address [default: localhost:8080]
+        addr: Option<String>,
+
+        /// Retry a failed connect up to N times with exponential backoff
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        retry: u32,
+    },
+}
+
 // Diffie-Hellman parameters (hardcoded to avoid randomness issues)
-const DH_P: u64 = 0xD87FA3E29184C7F3; // 64-bit prime
-const DH_G: u64 = 2;                  // Generator
+/// RFC 3526 group 14: 2048-bit MODP prime
+const MODP_2048_P: &str = concat!(
+    "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+    "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+    "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+    "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+    "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+    "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+    "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+    "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+    "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+    "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+    "15728E5A8AACAA68FFFFFFFFFFFFFFFF",
+);
+const WEAK_DH_P: u64 = 0xD87FA3E29184C7F3; // 64-bit prime (--weak-dh)
+const DH_G: u32 = 2;                       // Generator
+
+/// Public keys on the wire: a 2-byte little-endian length, then the
+/// big-endian integer. Anything longer than the 2048-bit group is rejected.
+const MAX_PUBLIC_KEY_LEN: usize = 256;
+
+/// Modulus and generator, plus how many random bits a private key gets
+struct DhGroup {
+    p: BigUint,
+    g: BigUint,
+    private_bits: u64,
+    weak: bool,
+}
+
+impl DhGroup {
+    fn new(weak: bool) -> Self {
+        let p = if weak {
+            BigUint::from(WEAK_DH_P)
+        } else {
+            BigUint::parse_bytes(MODP_2048_P.as_bytes(), 16).expect("valid MODP prime")
+        };
+
+        DhGroup {
+            p,
+            g: BigUint::from(DH_G),
+            private_bits: if weak { 64 } else { 256 },
+            weak,
+        }
+    }
+}
 
 /// Modular exponentiation: base^exp mod modulus using square-and-multiply
-fn mod_exp(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
-    let mut result = 1u64;
-    base %= modulus;
+fn mod_exp(base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+    let base = base % modulus;
+    let mut result = BigUint::from(1u32);
 
-    while exp > 0 {
-        if exp % 2 == 1 {
-            result = ((result as u128 * base as u128) % modulus as u128) as u64;
+    for bit in (0..exp.bits()).rev() {
+        result = &result * &result % modulus;
+        if exp.bit(bit) {
+            result = &result * &base % modulus;
         }
-        exp >>= 1;
-        base = ((base as u128 * base as u128) % modulus as u128) as u64;
     }
-    result
+    result % modulus
 }
 
-/// LCG keystream generator
-fn generate_keystream(seed: u64, length: usize) -> Vec<u8> {
-    let mut rng = LCG::new(seed);
-    (0..length).map(|_| rng.next()).collect()
+/// Hex for --explain: small values in full, big ones shortened with their size
+fn dh_hex(value: &BigUint) -> String {
+    let hex = format!("{:016x}", value);
+    if hex.len() > 32 {
+        format!("{}...{} ({} bits)", &hex[..16], &hex[hex.len() - 16..], value.bits())
+    } else {
+        hex
+    }
+}
+
+fn write_public_key(stream: &mut impl Write, key: &BigUint) -> io::Result<()> {
+    let bytes = key.to_bytes_be();
+    stream.write_all(&(bytes.len() as u16).to_le_bytes())?;
+    stream.write_all(&bytes)
+}
+
+fn read_public_key(stream: &mut impl Read) -> io::Result<BigUint> {
+    let mut len_bytes = [0u8; 2];
+    stream.read_exact(&mut len_bytes)?;
+
+    let len = u16::from_le_bytes(len_bytes) as usize;
+    if len == 0 || len > MAX_PUBLIC_KEY_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid public key length {}", len),
+        ));
+    }
+
+    let mut bytes = vec![0u8; len];
+    stream.read_exact(&mut bytes)?;
+    Ok(BigUint::from_bytes_be(&bytes))
+}
+
+/// Source of keystream bytes, continuing from where the last call stopped
+trait KeystreamGenerator: Send {
+    fn fill(&mut self, buf: &mut [u8]);
+}
+
+/// ChaCha20 with a zero nonce (every key is used for one stream only)
+struct ChaChaKeystream(ChaCha20);
+
+impl ChaChaKeystream {
+    /// Keyed by SHA-256 of a 64-bit seed
+    fn new(seed: u64) -> Self {
+        Self::with_key(Sha256::digest(seed.to_le_bytes()).into())
+    }
+
+    fn with_key(key: [u8; 32]) -> Self {
+        ChaChaKeystream(ChaCha20::new(&key.into(), &[0u8; 12].into()))
+    }
 }
 
-struct LCG {
+impl KeystreamGenerator for ChaChaKeystream {
+    fn fill(&mut self, buf: &mut [u8]) {
+        buf.fill(0);
+        self.0.apply_keystream(buf);
+    }
+}
+
+/// Legacy LCG keystream generator (predictable, kept for --legacy-cipher)
+struct Lcg {
     state: u64,
 }
 
-impl LCG {
+impl Lcg {
     fn new(seed: u64) -> Self {
-        LCG { state: seed }
+        Lcg { state: seed }
     }
 
     fn next(&mut self) -> u8 {
@@ -42,6 +338,310 @@ impl LCG {
     }
 }
 
+impl KeystreamGenerator for Lcg {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            *b = self.next();
+        }
+    }
+}
+
+/// One direction of the channel: the keystream keeps running across messages
+/// so that no keystream byte is ever used twice
+struct Keystream {
+    generator: Box<dyn KeystreamGenerator>,
+    position: u64,
+}
+
+impl Keystream {
+    fn new(seed: u64, legacy: bool) -> Self {
+        let generator: Box<dyn KeystreamGenerator> = if legacy {
+            Box::new(Lcg::new(seed))
+        } else {
+            Box::new(ChaChaKeystream::new(seed))
+        };
+        Keystream { generator, position: 0 }
+    }
+
+    /// Server-to-client and client-to-server traffic get distinct keys
+    fn for_direction(secret: &SessionSecret, from_server: bool, legacy: bool) -> Self {
+        match secret {
+            SessionSecret::Full(bytes) if !legacy => Keystream {
+                generator: Box::new(ChaChaKeystream::with_key(derive_key(b"streamchat key", bytes, from_server))),
+                position: 0,
+            },
+            _ => {
+                let seed = secret.seed();
+                Self::new(if from_server { seed } else { !seed }, legacy)
+            }
+        }
+    }
+
+    /// The next `length` keystream bytes, with the position of the first one
+    fn next_bytes(&mut self, length: usize) -> (u64, Vec<u8>) {
+        let start = self.position;
+        self.position += length as u64;
+        let mut bytes = vec![0u8; length];
+        self.generator.fill(&mut bytes);
+        (start, bytes)
+    }
+}
+
+/// What the per-direction keystream and MAC keys are derived from
+enum SessionSecret {
+    /// A 64-bit seed: the --weak-dh secret, or a --encrypt/--decrypt one
+    Seed(u64),
+    /// The fixed-width big-endian secret of the 2048-bit group
+    Full(Vec<u8>),
+}
+
+impl SessionSecret {
+    /// 64-bit seed, for the LCG (which can't use more): a full secret is
+    /// reduced to the first 8 bytes of its SHA-256
+    fn seed(&self) -> u64 {
+        match self {
+            SessionSecret::Seed(seed) => *seed,
+            SessionSecret::Full(bytes) => {
+                u64::from_le_bytes(Sha256::digest(bytes)[..8].try_into().unwrap())
+            }
+        }
+    }
+}
+
+/// SHA-256 of a purpose label, the direction and the whole shared secret
+fn derive_key(purpose: &[u8], secret: &[u8], from_server: bool) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(purpose)
+        .chain_update([from_server as u8])
+        .chain_update(secret)
+        .finalize()
+        .into()
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 tag appended to every ciphertext
+const MAC_LEN: usize = 32;
+
+/// Per-direction MAC key, derived from the shared secret separately from the keystream
+fn mac_key(secret: &SessionSecret, from_server: bool) -> [u8; 32] {
+    match secret {
+        SessionSecret::Seed(seed) => Sha256::new()
+            .chain_update(b"streamchat mac")
+            .chain_update(seed.to_le_bytes())
+            .chain_update([from_server as u8])
+            .finalize()
+            .into(),
+        SessionSecret::Full(bytes) => derive_key(b"streamchat mac", bytes, from_server),
+    }
+}
+
+/// The tag covers the direction and the keystream position of the message
+/// along with its ciphertext, so a replayed, reordered or dropped frame fails
+/// verification instead of decrypting to garbage
+fn message_mac(key: &[u8], from_server: bool, position: u64, cipher: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&[from_server as u8]);
+    mac.update(&position.to_le_bytes());
+    mac.update(cipher);
+    mac
+}
+
+/// Ciphertext followed by its tag
+fn seal(mut cipher: Vec<u8>, key: &[u8], from_server: bool, position: u64) -> Vec<u8> {
+    let tag = message_mac(key, from_server, position, &cipher).finalize().into_bytes();
+    cipher.extend_from_slice(&tag);
+    cipher
+}
+
+/// The ciphertext of a sealed payload, or None if its tag doesn't match the
+/// expected direction and position
+fn unseal<'a>(payload: &'a [u8], key: &[u8], from_server: bool, position: u64) -> Option<&'a [u8]> {
+    let (cipher, tag) = payload.split_at(payload.len().checked_sub(MAC_LEN)?);
+    message_mac(key, from_server, position, cipher)
+        .verify_slice(tag)
+        .ok()
+        .map(|_| cipher)
+}
+
+/// Verify then decrypt one sealed payload, returning the keystream position
+/// and bytes used along with the plaintext. A payload that fails the MAC is
+/// rejected before any keystream is used, so the next genuine one still opens.
+fn open_payload(payload: &[u8], keystream: &mut Keystream, key: &[u8], from_server: bool) -> Option<(u64, Vec<u8>, Vec<u8>)> {
+    let cipher = unseal(payload, key, from_server, keystream.position)?;
+    let (position, stream_bytes) = keystream.next_bytes(cipher.len());
+    Some((position, stream_bytes.clone(), xor_cipher(cipher, &stream_bytes)))
+}
+
+/// Our half of the channel: encrypts, tags and frames outgoing messages
+struct Outgoing {
+    writer: Arc<Mutex<TcpStream>>,
+    keystream: Keystream,
+    mac_key: [u8; 32],
+    from_server: bool,
+}
+
+impl Outgoing {
+    /// Send one message of the given kind; returns the keystream position and
+    /// bytes used along with the ciphertext (for --explain and --replay)
+    fn send(&mut self, kind: u8, body: &[u8]) -> io::Result<(u64, Vec<u8>, Vec<u8>)> {
+        let mut plain = Vec::with_capacity(body.len() + 1);
+        plain.push(kind);
+        plain.extend_from_slice(body);
+
+        let (position, keystream) = self.keystream.next_bytes(plain.len());
+        let cipher = xor_cipher(&plain, &keystream);
+        let sealed = seal(cipher.clone(), &self.mac_key, self.from_server, position);
+        write_frame(&mut *self.writer.lock().unwrap(), &Frame::Data(sealed))?;
+        Ok((position, keystream, cipher))
+    }
+}
+
+/// Transfer progress lines for --progress: the first chunk, at most one per
+/// interval after it, and the last one. Quiet without --progress.
+struct Progress {
+    interval: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Progress {
+    fn new(interval: Option<Duration>) -> Self {
+        Progress { interval, last: None }
+    }
+
+    fn report(&mut self, label: &str, verb: &str, done: u64, total: u64) {
+        if let Some(line) = self.line(label, verb, done, total) {
+            println!("{}", line);
+        }
+    }
+
+    /// The progress line due after `done` bytes, if any
+    fn line(&mut self, label: &str, verb: &str, done: u64, total: u64) -> Option<String> {
+        let interval = self.interval?;
+        if done < total && self.last.is_some_and(|last| last.elapsed() < interval) {
+            return None;
+        }
+        self.last = Some(Instant::now());
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        Some(format!("{} [FILE] {} {}/{} bytes ({}%)", label, verb, done, total, percent))
+    }
+}
+
+/// Stream a file to the peer chunk by chunk. Local file errors are reported
+/// and abort the transfer, but keep the chat going.
+fn send_file(outgoing: &mut Outgoing, path: &str, label: &str, progress: Option<Duration>) -> io::Result<()> {
+    let (mut file, size) = match fs::File::open(path).and_then(|f| Ok((f.metadata()?.len(), f))) {
+        Ok((size, file)) => (file, size),
+        Err(e) => {
+            println!("{} [FILE] Cannot open {}: {}", label, path, e);
+            return Ok(());
+        }
+    };
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+
+    let mut header = size.to_le_bytes().to_vec();
+    header.extend_from_slice(name.as_bytes());
+    outgoing.send(MSG_FILE_START, &header)?;
+    println!("{} [FILE] Sending {} ({} bytes)", label, name, size);
+
+    let mut chunk = vec![0u8; FILE_CHUNK_SIZE];
+    let mut sent = 0u64;
+    let mut hasher = Sha256::new();
+    let mut progress = Progress::new(progress);
+    loop {
+        let n = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                // The receiver notices the short file when the transfer ends
+                println!("{} [FILE] Cannot read {}: {}", label, path, e);
+                break;
+            }
+        };
+        outgoing.send(MSG_FILE_CHUNK, &chunk[..n])?;
+        hasher.update(&chunk[..n]);
+        sent += n as u64;
+        progress.report(label, "Sent", sent, size);
+    }
+
+    outgoing.send(MSG_FILE_END, &hasher.finalize())?;
+    println!("{} [FILE] Sent {} ({} bytes)", label, name, sent);
+    Ok(())
+}
+
+/// A file being received (into DOWNLOAD_DIR)
+struct Download {
+    file: fs::File,
+    path: PathBuf,
+    expected: u64,
+    received: u64,
+    hasher: Sha256,
+    progress: Progress,
+}
+
+impl Download {
+    /// Create the target file in `dir` from a MSG_FILE_START body, never overwriting an existing one
+    fn start(dir: &Path, header: &[u8], progress: Option<Duration>) -> io::Result<Self> {
+        if header.len() < 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated file header"));
+        }
+        let (size, name) = header.split_at(8);
+        let expected = u64::from_le_bytes(size.try_into().unwrap());
+
+        // Only keep the last path component: the peer doesn't choose where we write
+        let name = String::from_utf8_lossy(name);
+        let name = Path::new(name.as_ref())
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "download".to_string());
+
+        // create_new checks and creates in one step, so a file appearing
+        // meanwhile is skipped rather than truncated
+        fs::create_dir_all(dir)?;
+        let mut path = dir.join(&name);
+        let mut n = 1;
+        let file = loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    path = dir.join(format!("{}.{}", name, n));
+                    n += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        Ok(Download {
+            file,
+            path,
+            expected,
+            received: 0,
+            hasher: Sha256::new(),
+            progress: Progress::new(progress),
+        })
+    }
+
+    /// Check the received size and the SHA-256 from MSG_FILE_END, reporting the outcome
+    fn finish(self, checksum: &[u8], label: &str) {
+        if self.received != self.expected {
+            println!(
+                "{} [FILE] Incomplete {}: got {} of {} bytes",
+                label,
+                self.path.display(),
+                self.received,
+                self.expected
+            );
+        } else if self.hasher.finalize().as_slice() != checksum {
+            println!("{} [FILE] Checksum mismatch for {}, the file is corrupt", label, self.path.display());
+        } else {
+            println!("{} [FILE] Saved {} ({} bytes, SHA-256 verified)", label, self.path.display(), self.received);
+        }
+    }
+}
+
 /// XOR encrypt/decrypt with keystream
 fn xor_cipher(data: &[u8], keystream: &[u8]) -> Vec<u8> {
     data.iter()
@@ -51,210 +651,1101 @@ fn xor_cipher(data: &[u8], keystream: &[u8]) -> Vec<u8> {
 }
 
 struct DHSession {
-    private_key: u64,
-    public_key: u64,
-    shared_secret: Option<u64>,
+    group: DhGroup,
+    private_key: BigUint,
+    public_key: BigUint,
+    shared_secret: Option<BigUint>,
 }
 
 impl DHSession {
-    fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        let private_key = rng.gen::<u64>();
-        let public_key = mod_exp(DH_G, private_key, DH_P);
+    fn new(group: DhGroup) -> Self {
+        let mut bytes = vec![0u8; group.private_bits as usize / 8];
+        rand::thread_rng().fill(&mut bytes[..]);
+        Self::with_private_key(group, BigUint::from_bytes_be(&bytes))
+    }
+
+    /// Deterministic session from a known private key
+    fn with_private_key(group: DhGroup, private_key: BigUint) -> Self {
+        let public_key = mod_exp(&group.g, &private_key, &group.p);
 
         DHSession {
+            group,
             private_key,
             public_key,
             shared_secret: None,
         }
     }
 
-    fn compute_shared_secret(&mut self, their_public_key: u64) {
-        let secret = mod_exp(their_public_key, self.private_key, DH_P);
+    /// Rejects keys outside 2..p-2, which would force a trivial secret
+    /// (also what a peer using the other group size looks like)
+    fn compute_shared_secret(&mut self, their_public_key: &BigUint) -> Result<(), String> {
+        let one = BigUint::from(1u32);
+        if *their_public_key <= one || *their_public_key >= &self.group.p - &one {
+            return Err(format!("Invalid peer public key {} (is the peer using --weak-dh too?)", dh_hex(their_public_key)));
+        }
+
+        let secret = mod_exp(their_public_key, &self.private_key, &self.group.p);
         self.shared_secret = Some(secret);
+        Ok(())
+    }
+
+    /// What the session keys are derived from: the secret itself as a seed
+    /// with the weak group, otherwise its whole fixed-width encoding
+    fn session_secret(&self) -> Option<SessionSecret> {
+        let secret = self.shared_secret.as_ref()?;
+
+        if self.group.weak {
+            return Some(SessionSecret::Seed(secret.iter_u64_digits().next().unwrap_or(0)));
+        }
+
+        // Pad to the width of p so leading zero bytes count too
+        let secret_bytes = secret.to_bytes_be();
+        let mut bytes = vec![0u8; self.group.p.to_bytes_be().len() - secret_bytes.len()];
+        bytes.extend(secret_bytes);
+        Some(SessionSecret::Full(bytes))
+    }
+}
+
+/// Parse a hex u64 with an optional 0x prefix
+fn parse_hex_u64(text: &str) -> Option<u64> {
+    let hex = text.trim();
+    let hex = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X")).unwrap_or(hex);
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Parse a private key file containing a single hex u64 (optional 0x prefix)
+fn load_private_key(path: &str) -> Result<u64, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read seed file: {}", e))?;
+    parse_hex_u64(&content).ok_or_else(|| format!("Invalid hex key in seed file: {}", content.trim()))
+}
+
+/// Standalone cipher: XOR all of stdin with the keystream for `secret` and
+/// write the result to stdout. The XOR is its own inverse, so this both
+/// encrypts and decrypts.
+fn pipe_cipher(secret: &str, legacy: bool) -> Result<(), String> {
+    let secret = parse_hex_u64(secret).ok_or_else(|| format!("Invalid hex secret: {}", secret))?;
+
+    let mut data = Vec::new();
+    io::stdin()
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Cannot read stdin: {}", e))?;
+
+    let (_, keystream) = Keystream::new(secret, legacy).next_bytes(data.len());
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(&xor_cipher(&data, &keystream))
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("Cannot write stdout: {}", e))
+}
+
+/// Input channels of the connected clients, by session id
+type Sessions = Arc<Mutex<HashMap<usize, mpsc::Sender<String>>>>;
+
+/// Server side of one client connection
+struct ServerSession {
+    id: usize,
+    /// Lines to send to this client: typed on the server or relayed from other clients
+    input: mpsc::Receiver<String>,
+    peers: Sessions,
+}
+
+/// Queue a line for every session except `from`, dropping sessions that are gone
+fn broadcast(peers: &Sessions, from: Option<usize>, line: &str) {
+    peers
+        .lock()
+        .unwrap()
+        .retain(|&id, tx| Some(id) == from || tx.send(line.to_string()).is_ok());
+}
+
+/// Our name as sent in the hello message: at most 255 bytes, cut at a char boundary
+fn name_bytes(name: &str) -> &[u8] {
+    let mut end = name.len().min(u8::MAX as usize);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name.as_bytes()[..end]
+}
+
+/// The peer's name from its hello message, or None if it is empty or not valid UTF-8
+fn parse_name(body: &[u8]) -> Option<String> {
+    std::str::from_utf8(body)
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Space-separated hex bytes
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// The first bytes for --explain dumps, "..." marking that more follow
+fn hex_preview(bytes: &[u8]) -> String {
+    const SHOWN: usize = 4;
+    if bytes.len() > SHOWN {
+        format!("{} ...", hex_bytes(&bytes[..SHOWN]))
+    } else {
+        hex_bytes(bytes)
+    }
+}
+
+/// I/O errors that only mean the peer went away
+fn is_disconnect(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// The --explain narration of a finished key exchange; nothing without --explain
+fn handshake_explanation(
+    explain: bool,
+    label: &str,
+    dh: &DHSession,
+    their_public_key: &BigUint,
+    secret: &SessionSecret,
+    legacy_cipher: bool,
+) -> Vec<String> {
+    if !explain {
+        return vec![];
+    }
+
+    let mut lines = vec![
+        label.to_string(),
+        format!("{} Starting key exchange...", label),
+        format!("{} Using hardcoded DH parameters:", label),
+    ];
+    if dh.group.weak {
+        lines.push(format!("{} p = D87F A3E2 9184 C7F3 (64-bit prime - public)", label));
+    } else {
+        lines.push(format!("{} p = RFC 3526 group 14 (2048-bit MODP prime - public)", label));
+    }
+    lines.extend([
+        format!("{} g = 2 (Generator - public)", label),
+        String::new(),
+        format!("{} Generating our keypair...", label),
+        format!("private_key = {} (Random {}-bit)", dh_hex(&dh.private_key), dh.group.private_bits),
+        "public_key = g^private_key mod p".to_string(),
+        format!("          = 2^{} mod p", dh_hex(&dh.private_key)),
+        format!("          = {}", dh_hex(&dh.public_key)),
+        String::new(),
+        format!("{} [DH] Exchanging keys...", label),
+        format!("{} [NETWORK] Sending public key ({} bytes)...", label, dh.public_key.to_bytes_be().len()),
+        format!("{} - Send our public: {}", label, dh_hex(&dh.public_key)),
+        format!("{} [NETWORK] Received public key ({} bytes) /", label, their_public_key.to_bytes_be().len()),
+        format!("{} - Receive their public: {}", label, dh_hex(their_public_key)),
+        String::new(),
+        format!("{} [DH] Computing shared secret...", label),
+        format!("{} Formula: secret = (their_public)^(our_private) mod p", label),
+        String::new(),
+        format!("secret = ({})^({}) mod p", dh_hex(their_public_key), dh_hex(&dh.private_key)),
+        format!("       = {}", dh.shared_secret.as_ref().map(dh_hex).unwrap_or_default()),
+        String::new(),
+        format!("{} [VERIFY] Both sides computed the same secret /", label),
+        String::new(),
+        format!("{} [STREAM] Generating keystream from secret...", label),
+    ]);
+
+    match (secret, legacy_cipher) {
+        (SessionSecret::Full(_), false) => {
+            lines.push("Algorithm: ChaCha20, key = SHA-256(\"streamchat key\" || direction || secret), nonce = 0".to_string());
+            lines.push("MAC key: SHA-256(\"streamchat mac\" || direction || secret)".to_string());
+        }
+        (_, legacy) => {
+            if legacy {
+                lines.push("Algorithm: LCG (a=1103515245, c=12345, m=2^32)".to_string());
+            } else {
+                lines.push("Algorithm: ChaCha20, key = SHA-256(seed), nonce = 0".to_string());
+            }
+            let seed = secret.seed();
+            lines.push(format!("Seed: secret = {:016x} (server to client), !secret = {:016x} (client to server)", seed, !seed));
+        }
     }
+    lines
 }
 
-fn handle_client(mut stream: TcpStream, is_server: bool) {
+/// Run a chat session, reporting a peer hanging up as a normal end
+fn handle_client(stream: TcpStream, is_server: bool, config: &ChatConfig, session: Option<ServerSession>) {
     let label = if is_server { "[SERVER]" } else { "[CLIENT]" };
+    match chat(stream, label, is_server, config, session) {
+        Ok(()) => {}
+        Err(e) if is_disconnect(&e) => println!("{} Peer disconnected", label),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+fn chat(mut stream: TcpStream, label: &'static str, is_server: bool, config: &ChatConfig, session: Option<ServerSession>) -> io::Result<()> {
+    let explain = config.explain;
 
-    println!("{} Connected from {}", label, stream.peer_addr().unwrap());
+    println!("{} Connected from {}", label, stream.peer_addr()?);
 
     // Initialize Diffie-Hellman
-    let mut dh = DHSession::new();
-    println!("{}", label);
-    println!("{} Starting key exchange...", label);
-    println!("{} Using hardcoded DH parameters:", label);
-    println!("{} p = D87F A3E2 9184 C7F3 (64-bit prime - public)", label);
-    println!("{} g = 2 (Generator - public)", label);
-    println!();
-    println!("{} Generating our keypair...", label);
-    println!("private_key = {:016x} (Random 64-bit)", label);
-    println!("public_key = g^private_key mod p", label);
-    println!("          = 2^{:x} mod 0xD87FA3E29184C7F3", dh.private_key);
-    println!("          = {:016x}", dh.public_key);
-    println!();
+    let group = DhGroup::new(config.weak_dh);
+    let mut dh = match config.private_key {
+        Some(key) => DHSession::with_private_key(group, BigUint::from(key)),
+        None => DHSession::new(group),
+    };
 
-    // Send our public key (8 bytes)
-    let pub_key_bytes = dh.public_key.to_le_bytes();
-    stream.write_all(&pub_key_bytes).unwrap();
-    println!("{} [DH] Exchanging keys...", label);
-    println!("{} [NETWORK] Sending public key (8 bytes)...", label);
-    println!("{} - Send our public: {:016x}", label, dh.public_key);
-
-    // Receive their public key
-    let mut buffer = [0u8; 8];
-    stream.read_exact(&mut buffer).unwrap();
-    let their_public_key = u64::from_le_bytes(buffer);
-    println!("{} [NETWORK] Received public key (8 bytes) /", label);
-    println!("{} - Receive their public: {:016x}", label, their_public_key);
-    println!();
+    // Send our public key (length-prefixed big-endian), receive theirs
+    write_public_key(&mut stream, &dh.public_key)?;
+    let their_public_key = read_public_key(&mut stream)?;
 
     // Compute shared secret
-    dh.compute_shared_secret(their_public_key);
-    let secret = dh.shared_secret.unwrap();
+    dh.compute_shared_secret(&their_public_key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let secret = dh.session_secret().unwrap();
 
-    println!("{} [DH] Computing shared secret...", label);
-    println!("{} Formula: secret = (their_public)^(our_private) mod p", label);
-    println!();
-    println!("secret = ({:016x})^({:016x}) mod 0xD87FA3E29184C7F3", their_public_key, dh.private_key);
-    println!("       = {:016x}", secret);
-    println!();
-    println!("{} [VERIFY] Both sides computed the same secret /", label);
-    println!();
+    for line in handshake_explanation(explain, label, &dh, &their_public_key, &secret, config.legacy_cipher) {
+        println!("{}", line);
+    }
+
+    // Each direction has its own keystream, so the sending loop below and the
+    // receiving thread never share cipher state
+    let send_keystream = Keystream::for_direction(&secret, is_server, config.legacy_cipher);
+    let mut recv_keystream = Keystream::for_direction(&secret, !is_server, config.legacy_cipher);
+    let send_mac_key = mac_key(&secret, is_server);
+    let recv_mac_key = mac_key(&secret, !is_server);
 
-    // Generate keystream
-    println!("{} [STREAM] Generating keystream from secret...", label);
-    println!("Algorithm: LCG (a=1103515245, c=12345, m=2^32)", label);
-    println!("Seed: secret = {:016x}", secret);
+    let reader = stream.try_clone()?;
+    let writer = Arc::new(Mutex::new(stream));
+    let awaiting_pong = Arc::new(AtomicBool::new(false));
+    let incoming = spawn_reader(reader, Arc::clone(&writer), Arc::clone(&awaiting_pong));
+    let mut outgoing = Outgoing {
+        writer: Arc::clone(&writer),
+        keystream: send_keystream,
+        mac_key: send_mac_key,
+        from_server: is_server,
+    };
+
+    // Names: the first message each way, sealed like any other so nobody on the
+    // path can read or rewrite them. Without --name we send an empty name and
+    // the peer picks its own default
+    let default_peer = match &session {
+        Some(session) => format!("CLIENT #{}", session.id),
+        None => "SERVER".to_string(),
+    };
+    outgoing.send(MSG_TEXT, name_bytes(config.name.as_deref().unwrap_or("")))?;
+    let hello = incoming.recv().map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    let peer_name = match open_payload(&hello, &mut recv_keystream, &recv_mac_key, !is_server) {
+        Some((_, _, plain)) if plain.first() == Some(&MSG_TEXT) => parse_name(&plain[1..]).unwrap_or(default_peer),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "the peer's name failed authentication")),
+    };
+    if explain {
+        println!("{} [HELLO] Peer is {}", label, peer_name);
+    }
 
     // Chat loop
     println!();
     println!("{} / Secure channel established!", label);
     println!();
-    println!("{} [CHAT] Type message:", label);
+    println!("{} [CHAT] Type message (/send PATH to send a file):", label);
+
+    if let Some(interval) = config.keepalive {
+        spawn_keepalive(Arc::clone(&writer), awaiting_pong, interval, label);
+    }
+
+    // Receive and print messages as they arrive, independently of what we send
+    let relay = session.as_ref().map(|session| (session.id, Arc::clone(&session.peers)));
+    let progress = config.progress;
+    let receiver = thread::spawn(move || {
+        let mut download: Option<Download> = None;
+        // The reader thread drops the channel once the socket is closed
+        for payload in incoming {
+            // Verify before decrypting: XOR ciphertext is trivially malleable
+            let Some((recv_position, recv_key, recv_plain)) = open_payload(&payload, &mut recv_keystream, &recv_mac_key, !is_server) else {
+                println!("{} Message authentication failed, message dropped", label);
+                continue;
+            };
+            let recv_cipher = &payload[..payload.len() - MAC_LEN];
+            let recv_len = recv_cipher.len();
+            let Some((&kind, body)) = recv_plain.split_first() else {
+                continue;
+            };
+
+            match kind {
+                MSG_TEXT => {}
+                MSG_FILE_START => {
+                    match Download::start(Path::new(DOWNLOAD_DIR), body, progress) {
+                        Ok(started) => {
+                            println!("{} [FILE] Receiving {} ({} bytes)", label, started.path.display(), started.expected);
+                            download = Some(started);
+                        }
+                        Err(e) => println!("{} [FILE] Cannot save incoming file: {}", label, e),
+                    }
+                    continue;
+                }
+                MSG_FILE_CHUNK => {
+                    if let Some(current) = download.as_mut() {
+                        if let Err(e) = current.file.write_all(body) {
+                            println!("{} [FILE] Cannot write {}: {}", label, current.path.display(), e);
+                            download = None;
+                        } else {
+                            current.hasher.update(body);
+                            current.received += body.len() as u64;
+                            current.progress.report(label, "Received", current.received, current.expected);
+                        }
+                    }
+                    continue;
+                }
+                MSG_FILE_END => {
+                    if let Some(done) = download.take() {
+                        done.finish(body, label);
+                    }
+                    continue;
+                }
+                other => {
+                    println!("{} Unknown message type {}, skipped", label, other);
+                    continue;
+                }
+            }
+            let recv_message = String::from_utf8_lossy(body);
+
+            if explain {
+                println!("{} [NETWORK] Received encrypted message ({} bytes)", label, recv_len);
+                println!("{} [-] Received {} bytes", label, recv_len);
+                println!();
+                println!("{} [DECRYPT]", label);
+                println!("Cipher: {}", hex_preview(recv_cipher));
+                println!("Key: {} (keystream position: {})", hex_preview(&recv_key), recv_position);
+                println!("Plain: {}", recv_message);
+                println!();
+            }
+            match &relay {
+                Some((id, peers)) => {
+                    let line = format!("[{}] {}", peer_name, recv_message);
+                    println!("{}", line);
+                    // Other clients see it as a message from the server
+                    broadcast(peers, Some(*id), &line);
+                }
+                None => println!("[{}] {}", peer_name, recv_message),
+            }
+        }
+
+        println!("{} Peer disconnected", label);
+        match relay {
+            // Closing our input channel ends the sending loop of this session
+            Some((id, peers)) => {
+                peers.lock().unwrap().remove(&id);
+            }
+            // Nothing left to chat with; don't wait for the next typed line
+            None => std::process::exit(0),
+        }
+    });
 
     let stdin = std::io::stdin();
     let mut input = String::new();
+    let mut replay = config.replay.as_ref().map(|lines| lines.iter());
 
     loop {
         input.clear();
-        stdin.read_line(&mut input).unwrap();
+        match replay.as_mut() {
+            // Scripted session: the transcript replaces typed input
+            Some(lines) => match lines.next() {
+                Some(line) => input.push_str(line),
+                None => {
+                    println!("{} [REPLAY] Transcript finished", label);
+                    break;
+                }
+            },
+            None => match &session {
+                Some(session) => match session.input.recv() {
+                    Ok(line) => input.push_str(&line),
+                    Err(_) => break,
+                },
+                None => {
+                    if stdin.read_line(&mut input)? == 0 {
+                        println!("{} Input closed", label);
+                        break;
+                    }
+                }
+            },
+        }
         let message = input.trim();
 
         if message.is_empty() {
             continue;
         }
 
-        // Encrypt message
-        let keystream = generate_keystream(secret, message.len());
-        let cipher = xor_cipher(message.as_bytes(), &keystream);
+        if let Some(path) = message.strip_prefix("/send ") {
+            send_file(&mut outgoing, path.trim(), label, config.progress)?;
+            continue;
+        }
 
-        println!();
-        println!("{} [ENCRYPT]", label);
-        println!("Plain: {}", message);
-        println!("Key: {:02x} {:02x} {:02x} {:02x} (keystream position: 0)", 
-                 keystream[0], keystream[1], keystream[2], keystream[3]);
-        println!("Cipher: {:?} (keystream XOR)", cipher.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "));
-
-        // Send encrypted message
-        let len_bytes = (message.len() as u32).to_le_bytes();
-        stream.write_all(&len_bytes).unwrap();
-        stream.write_all(&cipher).unwrap();
-        println!();
-        println!("{} [NETWORK] Sending encrypted message ({} bytes)...", label, message.len());
-        println!("{} [-] Sent {} bytes", label, message.len());
-
-        // Receive encrypted message
-        stream.read_exact(&mut buffer[0..4]).unwrap();
-        let recv_len = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
-        let mut recv_cipher = vec![0u8; recv_len];
-        stream.read_exact(&mut recv_cipher).unwrap();
-
-        // Decrypt message
-        let recv_keystream = generate_keystream(secret, recv_len);
-        let recv_plain = xor_cipher(&recv_cipher, &recv_keystream);
-        let recv_message = String::from_utf8_lossy(&recv_plain);
-
-        println!("{} [NETWORK] Received encrypted message ({} bytes)", label, recv_len);
-        println!("{} [-] Received {} bytes", label, recv_len);
-        println!();
-        println!("{} [DECRYPT]", label);
-        println!("Cipher: {:02x} {:02x} {:02x} {:02x} ...", recv_cipher[0], recv_cipher[1], recv_cipher[2], recv_cipher[3]);
-        println!("Key: {:02x} {:02x} {:02x} {:02x} (keystream position: 0)", 
-                 recv_keystream[0], recv_keystream[1], recv_keystream[2], recv_keystream[3]);
-        println!("Plain: {}", recv_message);
-        println!();
-        println!("{} {}", if is_server { "[CLIENT]" } else { "[SERVER]" }, recv_message);
+        // Encrypt and send message
+        let (position, keystream, cipher) = outgoing.send(MSG_TEXT, message.as_bytes())?;
+
+        if explain {
+            println!();
+            println!("{} [ENCRYPT]", label);
+            println!("Plain: {}", message);
+            println!("Key: {} (keystream position: {})", hex_preview(&keystream), position);
+            println!("Cipher: {} (keystream XOR)", hex_bytes(&cipher));
+        }
+
+        if replay.is_some() {
+            println!("{} [REPLAY] {}", label, cipher.iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        }
+
+        if explain {
+            println!();
+            println!("{} [NETWORK] Sending encrypted message ({} bytes)...", label, message.len());
+            println!("{} [-] Sent {} bytes", label, message.len());
+        }
     }
+
+    // Done sending: let the peer see EOF, then wait for the rest of its messages
+    let _ = writer.lock().unwrap().shutdown(Shutdown::Write);
+    let _ = receiver.join();
+    Ok(())
 }
 
-fn run_server(port: u16) {
+fn run_server(port: u16, config: Arc<ChatConfig>) {
     let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
         .expect("Failed to bind");
     println!("[SERVER] Listening on 0.0.0.0:{}", port);
-    println!("[SERVER] Waiting for client...");
+    println!("[SERVER] Waiting for clients...");
     println!();
 
-    for stream in listener.incoming() {
+    // Lines typed on the server go to every connected client
+    let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+    let typed = Arc::clone(&sessions);
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+            broadcast(&typed, None, &line);
+        }
+    });
+
+    for (id, stream) in listener.incoming().enumerate() {
         match stream {
             Ok(stream) => {
-                handle_client(stream, true);
+                let (tx, rx) = mpsc::channel();
+                sessions.lock().unwrap().insert(id, tx);
+                let session = ServerSession { id, input: rx, peers: Arc::clone(&sessions) };
+                let config = Arc::clone(&config);
+                let sessions = Arc::clone(&sessions);
+
+                // One thread (and one DH handshake) per client; a client dropping only ends its thread
+                thread::spawn(move || {
+                    handle_client(stream, true, &config, Some(session));
+                    sessions.lock().unwrap().remove(&id);
+                    println!("[SERVER] Client #{} left", id);
+                });
             }
             Err(e) => eprintln!("Error: {}", e),
         }
     }
 }
 
-fn run_client(host: &str, port: u16) {
+/// First delay between connection attempts, doubled after each failure
+const RETRY_INITIAL_DELAY: Duration = Duration::from_millis(250);
+
+/// Connect, retrying up to `retries` extra times with exponential backoff
+fn connect_with_retry(addr: &str, retries: u32) -> io::Result<TcpStream> {
+    let mut delay = RETRY_INITIAL_DELAY;
+    let mut attempt = 0;
+    loop {
+        match TcpStream::connect(addr) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                println!("[CLIENT] Connect failed ({}), retry {}/{} in {:?}...", e, attempt, retries, delay);
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn run_client(host: &str, port: u16, retries: u32, config: &ChatConfig) {
     println!("[CLIENT] Connecting to {}:{}...", host, port);
-    match TcpStream::connect(format!("{}:{}", host, port)) {
+    match connect_with_retry(&format!("{}:{}", host, port), retries) {
         Ok(stream) => {
             println!("[CLIENT] Connected!");
             println!();
-            handle_client(stream, false);
+            handle_client(stream, false, config, None);
         }
         Err(e) => eprintln!("Error: {}", e),
     }
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args = Args::parse();
+
+    if let Some(secret) = args.encrypt.as_deref().or(args.decrypt.as_deref()) {
+        if let Err(e) = pipe_cipher(secret, args.legacy_cipher) {
+            eprintln!("Error: {}", e);
+        }
+        return;
+    }
 
-    if args.len() < 2 {
-        println!("Usage: streamchat <server|client> [HOST:PORT]");
+    let Some(command) = args.command else {
+        println!("Usage: streamchat [OPTIONS] <server|client> [HOST:PORT]");
+        println!("       streamchat --encrypt|--decrypt <HEX_SECRET> < INPUT > OUTPUT");
         println!();
         println!("Stream cipher chat with Diffie-Hellman key generation");
         println!();
         println!("Commands:");
         println!("  server              Start server");
         println!("  client              Connect to server");
+        println!();
+        println!("In the chat, /send PATH sends a file; the peer saves it under {}/", DOWNLOAD_DIR);
+        println!();
+        println!("Options:");
+        println!("  --explain           Explain the key exchange and show encrypt/decrypt dumps");
+        println!("  --keepalive SECS    Ping the peer every SECS seconds, close on no answer");
+        println!("  --replay FILE       Send the lines of FILE instead of reading stdin");
+        println!("  --encrypt HEX_SECRET  Encrypt stdin to stdout with the secret's keystream");
+        println!("  --decrypt HEX_SECRET  Decrypt stdin to stdout with the secret's keystream");
+        println!("  --progress [SECS]   Show file transfer progress every SECS seconds [default: 1]");
+        println!("  --name NAME         Name shown to the peer [default: SERVER or CLIENT]");
+        println!("  --legacy-cipher     Use the old LCG keystream instead of ChaCha20");
+        println!("  --weak-dh           Use the 64-bit DH group instead of 2048-bit MODP (demo only)");
+        println!("  --retry N           (client) Retry connecting N times with backoff");
         return;
-    }
+    };
 
-    match args[1].as_str() {
-        "server" => {
-            let port = if args.len() > 2 {
-                args[2].parse().unwrap_or(8080)
-            } else {
-                8080
-            };
-            run_server(port);
+    let private_key = match args.seed_file.as_deref().map(load_private_key).transpose() {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
         }
-        "client" => {
-            let addr = if args.len() > 2 {
-                args[2].to_string()
-            } else {
-                "localhost:8080".to_string()
-            };
+    };
+
+    let replay = match args.replay.as_deref().map(std::fs::read_to_string).transpose() {
+        Ok(content) => content.map(|c| c.lines().map(str::to_string).collect()),
+        Err(e) => {
+            eprintln!("Error: Cannot read replay file: {}", e);
+            return;
+        }
+    };
+
+    let config = ChatConfig {
+        explain: args.explain,
+        private_key,
+        keepalive: args.keepalive.map(Duration::from_secs),
+        replay,
+        legacy_cipher: args.legacy_cipher,
+        name: args.name,
+        progress: args.progress.map(Duration::from_secs),
+        weak_dh: args.weak_dh,
+    };
+
+    match command {
+        Command::Server { port } => {
+            let port = port.and_then(|p| p.parse().ok()).unwrap_or(8080);
+            run_server(port, Arc::new(config));
+        }
+        Command::Client { addr, retry } => {
+            let addr = addr.unwrap_or_else(|| "localhost:8080".to_string());
 
             let parts: Vec<&str> = addr.split(':').collect();
             let host = parts[0];
             let port = parts.get(1).and_then(|p| p.parse().ok()).unwrap_or(8080);
-            run_client(host, port);
+            run_client(host, port, retry, &config);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain_dumps_format_keys_and_bytes() {
+        assert_eq!(hex_bytes(&[0x00, 0xab, 0x10]), "00 ab 10");
+        assert_eq!(hex_bytes(&[]), "");
+        // 64-bit values print in full, zero padded
+        assert_eq!(dh_hex(&BigUint::from(0x1234u32)), "0000000000001234");
+        assert_eq!(dh_hex(&BigUint::from(WEAK_DH_P)), "d87fa3e29184c7f3");
+    }
+
+    #[test]
+    fn handshake_is_only_narrated_with_explain() {
+        let mut server = DHSession::with_private_key(DhGroup::new(true), BigUint::from(0x1234u32));
+        let client = DHSession::with_private_key(DhGroup::new(true), BigUint::from(0x5678u32));
+        server.compute_shared_secret(&client.public_key).unwrap();
+        let secret = server.session_secret().unwrap();
+
+        assert!(handshake_explanation(false, "[SERVER]", &server, &client.public_key, &secret, false).is_empty());
+
+        let lines = handshake_explanation(true, "[SERVER]", &server, &client.public_key, &secret, false);
+        assert!(lines.contains(&"[SERVER] p = D87F A3E2 9184 C7F3 (64-bit prime - public)".to_string()));
+        assert!(lines.contains(&format!("[SERVER] - Receive their public: {}", dh_hex(&client.public_key))));
+        assert!(lines.contains(&format!("       = {}", dh_hex(server.shared_secret.as_ref().unwrap()))));
+        assert_eq!(lines.last().unwrap(), &format!("Seed: secret = {:016x} (server to client), !secret = {:016x} (client to server)", secret.seed(), !secret.seed()));
+    }
+
+    #[test]
+    fn seed_file_holds_one_hex_key() {
+        assert_eq!(parse_hex_u64(" 0xDEADbeef\n"), Some(0xdeadbeef));
+        assert_eq!(parse_hex_u64("ff"), Some(0xff));
+        assert_eq!(parse_hex_u64("0x"), None);
+        assert_eq!(parse_hex_u64("12345678901234567"), None);
+
+        let path = std::env::temp_dir().join(format!("streamchat_{}_seed.txt", std::process::id()));
+        fs::write(&path, "0x2a\n").unwrap();
+        let loaded = load_private_key(path.to_str().unwrap());
+        fs::write(&path, "not hex").unwrap();
+        let invalid = load_private_key(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Ok(0x2a));
+        assert_eq!(invalid.unwrap_err(), "Invalid hex key in seed file: not hex");
+    }
+
+    #[test]
+    fn one_seed_file_pins_both_ends_to_the_same_keys() {
+        let path = std::env::temp_dir().join(format!("streamchat_{}_shared_seed.txt", std::process::id()));
+        fs::write(&path, "0x5eed\n").unwrap();
+        let seeded = || {
+            let key = load_private_key(path.to_str().unwrap()).unwrap();
+            DHSession::with_private_key(DhGroup::new(false), BigUint::from(key))
+        };
+        let (mut initiator, mut responder) = (seeded(), seeded());
+        fs::remove_file(&path).unwrap();
+
+        initiator.compute_shared_secret(&responder.public_key).unwrap();
+        responder.compute_shared_secret(&initiator.public_key).unwrap();
+        let (ours, theirs) = (initiator.session_secret().unwrap(), responder.session_secret().unwrap());
+
+        for from_server in [true, false] {
+            assert_eq!(mac_key(&ours, from_server), mac_key(&theirs, from_server));
+            let (_, sent) = Keystream::for_direction(&ours, from_server, false).next_bytes(32);
+            let (_, expected) = Keystream::for_direction(&theirs, from_server, false).next_bytes(32);
+            assert_eq!(sent, expected);
         }
-        _ => {
-            println!("Unknown command: {}", args[1]);
+    }
+
+    #[test]
+    fn control_frames_round_trip_next_to_data() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, &Frame::Data(b"hi".to_vec())).unwrap();
+        write_frame(&mut wire, &Frame::Ping).unwrap();
+        write_frame(&mut wire, &Frame::Pong).unwrap();
+
+        let mut reader = wire.as_slice();
+        assert!(matches!(read_frame(&mut reader), Ok(Frame::Data(data)) if data == b"hi"));
+        assert!(matches!(read_frame(&mut reader), Ok(Frame::Ping)));
+        assert!(matches!(read_frame(&mut reader), Ok(Frame::Pong)));
+        assert_eq!(read_frame(&mut reader).err().map(|e| e.kind()), Some(io::ErrorKind::UnexpectedEof));
+
+        let unknown = (CONTROL_FLAG | 7).to_le_bytes();
+        assert_eq!(read_frame(&mut unknown.as_slice()).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    fn tcp_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    fn outgoing(stream: TcpStream, secret: &SessionSecret, from_server: bool) -> Outgoing {
+        Outgoing {
+            writer: Arc::new(Mutex::new(stream)),
+            keystream: Keystream::for_direction(secret, from_server, false),
+            mac_key: mac_key(secret, from_server),
+            from_server,
+        }
+    }
+
+    /// The peer's side: verify and decrypt the next data frame into (kind, body)
+    fn receive(stream: &mut TcpStream, keystream: &mut Keystream, key: &[u8], from_server: bool) -> (u8, Vec<u8>) {
+        let Ok(Frame::Data(payload)) = read_frame(stream) else { panic!("expected a data frame") };
+        let (_, _, plain) = open_payload(&payload, keystream, key, from_server).expect("valid tag");
+        (plain[0], plain[1..].to_vec())
+    }
+
+    /// Weak-group session secret of two fixed private keys, as with --seed-file on both ends
+    fn fixed_secret() -> SessionSecret {
+        let mut server = DHSession::with_private_key(DhGroup::new(true), BigUint::from(0x1234u32));
+        let client = DHSession::with_private_key(DhGroup::new(true), BigUint::from(0x5678u32));
+        server.compute_shared_secret(&client.public_key).unwrap();
+        server.session_secret().unwrap()
+    }
+
+    #[test]
+    fn fixed_keys_replay_to_the_same_ciphertexts() {
+        let transcript = ["hello", "second line", "hello"];
+        let run = || {
+            let secret = fixed_secret();
+            let (server, mut client) = tcp_pair();
+            let mut sender = outgoing(server, &secret, true);
+            let mut keystream = Keystream::for_direction(&secret, true, false);
+            let key = mac_key(&secret, true);
+            transcript
+                .iter()
+                .map(|line| {
+                    let (_, _, cipher) = sender.send(MSG_TEXT, line.as_bytes()).unwrap();
+                    assert_eq!(receive(&mut client, &mut keystream, &key, true), (MSG_TEXT, line.as_bytes().to_vec()));
+                    cipher
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let ciphers = run();
+        assert_eq!(ciphers, run());
+        // The same line twice still encrypts differently: the keystream moved on
+        assert_ne!(ciphers[0], ciphers[2]);
+
+        // Golden transcript: with the all-zero ChaCha20 key, each kind byte and
+        // line is XORed with the next bytes of the RFC 7539 reference block
+        let (server, _client) = tcp_pair();
+        let mut pinned = Outgoing {
+            writer: Arc::new(Mutex::new(server)),
+            keystream: Keystream { generator: Box::new(ChaChaKeystream::with_key([0; 32])), position: 0 },
+            mac_key: [0; 32],
+            from_server: true,
+        };
+        let golden: Vec<Vec<u8>> = transcript
+            .iter()
+            .map(|line| pinned.send(MSG_TEXT, line.as_bytes()).unwrap().2)
+            .collect();
+        assert_eq!(golden, [
+            vec![0x76, 0xd0, 0x85, 0xc1, 0xcc, 0x9e],
+            vec![0x3d, 0xe3, 0x25, 0x3e, 0x05, 0x8b, 0x37, 0xa6, 0xd1, 0x41, 0xd3, 0xb7],
+            vec![0x19, 0xd0, 0xc5, 0xe1, 0x81, 0x75],
+        ]);
+    }
+
+    #[test]
+    fn connect_retries_then_gives_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        assert!(connect_with_retry(&addr, 0).is_ok());
+
+        drop(listener);
+        let started = Instant::now();
+        assert!(connect_with_retry(&addr, 1).is_err());
+        // One failed attempt, one backoff delay, one more failed attempt
+        assert!(started.elapsed() >= RETRY_INITIAL_DELAY);
+    }
+
+    #[test]
+    fn connect_retries_until_the_server_is_up() {
+        // Reserve a free port, then only start listening on it after the first retry delay
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let server = thread::spawn(move || {
+            thread::sleep(RETRY_INITIAL_DELAY + RETRY_INITIAL_DELAY / 2);
+            let listener = TcpListener::bind(addr).unwrap();
+            listener.accept().map(|_| ())
+        });
+
+        let started = Instant::now();
+        // Attempts at 0, 250 and 750 ms: the third one finds the listener
+        assert!(connect_with_retry(&addr.to_string(), 3).is_ok());
+        assert!(started.elapsed() >= RETRY_INITIAL_DELAY * 3);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn pipe_mode_keystream_is_its_own_inverse() {
+        let data = b"attack at dawn, attack at dawn";
+        for legacy in [false, true] {
+            let (_, encrypt_key) = Keystream::new(0xfeed, legacy).next_bytes(data.len());
+            let cipher = xor_cipher(data, &encrypt_key);
+            assert_ne!(cipher, data);
+            let (_, decrypt_key) = Keystream::new(0xfeed, legacy).next_bytes(data.len());
+            assert_eq!(xor_cipher(&cipher, &decrypt_key), data);
+
+            let (_, other_key) = Keystream::new(0xbeef, legacy).next_bytes(data.len());
+            assert_ne!(xor_cipher(&cipher, &other_key), data);
         }
     }
+
+    #[test]
+    fn progress_is_quiet_by_default_and_throttled() {
+        let mut quiet = Progress::new(None);
+        quiet.report("[TEST]", "Sent", 1, 2);
+        assert!(quiet.last.is_none());
+
+        let mut progress = Progress::new(Some(Duration::from_secs(3600)));
+        assert_eq!(progress.line("[TEST]", "Sent", 1, 3).as_deref(), Some("[TEST] [FILE] Sent 1/3 bytes (33%)"));
+        assert_eq!(progress.line("[TEST]", "Sent", 2, 3), None);
+        // Completion is always shown
+        assert_eq!(progress.line("[TEST]", "Sent", 3, 3).as_deref(), Some("[TEST] [FILE] Sent 3/3 bytes (100%)"));
+    }
+
+    #[test]
+    fn file_transfer_ends_with_the_content_checksum() {
+        let content: Vec<u8> = (0..40_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("streamchat_{}_transfer.bin", std::process::id()));
+        fs::write(&path, &content).unwrap();
+
+        let secret = fixed_secret();
+        let (server, mut client) = tcp_pair();
+        let mut sender = outgoing(server, &secret, true);
+        let source = path.to_str().unwrap().to_string();
+        let sending = thread::spawn(move || send_file(&mut sender, &source, "[TEST]", Some(Duration::from_secs(1))));
+
+        let mut keystream = Keystream::for_direction(&secret, true, false);
+        let key = mac_key(&secret, true);
+        let (kind, header) = receive(&mut client, &mut keystream, &key, true);
+        assert_eq!(kind, MSG_FILE_START);
+        assert_eq!(u64::from_le_bytes(header[..8].try_into().unwrap()), content.len() as u64);
+
+        let mut received = Vec::new();
+        let mut chunks = 0;
+        let mut progress = Progress::new(Some(Duration::from_secs(3600)));
+        let mut reported = vec![];
+        let checksum = loop {
+            match receive(&mut client, &mut keystream, &key, true) {
+                (MSG_FILE_CHUNK, body) => {
+                    received.extend(body);
+                    chunks += 1;
+                    reported.extend(progress.line("[TEST]", "Received", received.len() as u64, content.len() as u64));
+                }
+                (MSG_FILE_END, body) => break body,
+                (other, _) => panic!("unexpected message type {}", other),
+            }
+        };
+        sending.join().unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(received, content);
+        assert_eq!(checksum, Sha256::digest(&content).to_vec());
+        assert_eq!(chunks, 3);
+        assert_eq!(reported, [
+            "[TEST] [FILE] Received 16384/40000 bytes (40%)",
+            "[TEST] [FILE] Received 40000/40000 bytes (100%)",
+        ]);
+    }
+
+    #[test]
+    fn keystream_position_advances_across_messages() {
+        let mut split = Keystream::new(42, false);
+        let (first_at, first) = split.next_bytes(5);
+        let (second_at, second) = split.next_bytes(7);
+        assert_eq!((first_at, second_at, split.position), (0, 5, 12));
+
+        let (_, whole) = Keystream::new(42, false).next_bytes(12);
+        assert_eq!([first, second].concat(), whole);
+    }
+
+    /// RFC 7539 appendix A.1, test vector #1: ChaCha20 block 0 for an all-zero key and nonce
+    const RFC7539_ZERO_KEY_BLOCK: [u8; 64] = [
+        0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86, 0xbd, 0x28,
+        0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc, 0x8b, 0x77, 0x0d, 0xc7,
+        0xda, 0x41, 0x59, 0x7c, 0x51, 0x57, 0x48, 0x8d, 0x77, 0x24, 0xe0, 0x3f, 0xb8, 0xd8, 0x4a, 0x37,
+        0x6a, 0x43, 0xb8, 0xf4, 0x15, 0x18, 0xa1, 0x1c, 0xc3, 0x87, 0xb6, 0x69, 0xb2, 0xee, 0x65, 0x86,
+    ];
+
+    #[test]
+    fn chacha_matches_the_reference_keystream() {
+        let mut chacha = ChaChaKeystream::with_key([0; 32]);
+        let mut block = [0u8; 64];
+        chacha.fill(&mut block);
+        assert_eq!(block, RFC7539_ZERO_KEY_BLOCK);
+
+        let mut a = Lcg::new(7);
+        let mut b = Lcg::new(7);
+        let (mut left, mut right) = ([0u8; 8], [0u8; 8]);
+        a.fill(&mut left);
+        b.fill(&mut right);
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn broadcast_reaches_other_live_sessions() {
+        let peers: Sessions = Arc::default();
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+        let (tx_gone, rx_gone) = mpsc::channel();
+        drop(rx_gone);
+        peers.lock().unwrap().extend([(1, tx_a), (2, tx_b), (3, tx_gone)]);
+
+        broadcast(&peers, Some(1), "[alice] hi");
+        assert!(rx_a.try_recv().is_err());
+        assert_eq!(rx_b.try_recv().as_deref(), Ok("[alice] hi"));
+
+        let mut ids: Vec<usize> = peers.lock().unwrap().keys().copied().collect();
+        ids.sort();
+        assert_eq!(ids, [1, 2]);
+
+        broadcast(&peers, None, "from the server");
+        assert_eq!(rx_a.try_recv().as_deref(), Ok("from the server"));
+    }
+
+    #[test]
+    fn hang_ups_are_disconnects_other_errors_are_not() {
+        for kind in [io::ErrorKind::UnexpectedEof, io::ErrorKind::BrokenPipe, io::ErrorKind::ConnectionReset, io::ErrorKind::ConnectionAborted] {
+            assert!(is_disconnect(&io::Error::from(kind)), "{:?}", kind);
+        }
+        assert!(!is_disconnect(&io::Error::from(io::ErrorKind::InvalidData)));
+        assert!(!is_disconnect(&io::Error::from(io::ErrorKind::PermissionDenied)));
+
+        // What reading from a peer that hung up looks like
+        let (server, mut client) = tcp_pair();
+        drop(server);
+        assert!(read_frame(&mut client).err().is_some_and(|e| is_disconnect(&e)));
+    }
+
+    #[test]
+    fn reader_thread_forwards_data_and_answers_pings() {
+        let (server, mut client) = tcp_pair();
+        let writer = Arc::new(Mutex::new(server.try_clone().unwrap()));
+        let awaiting_pong = Arc::new(AtomicBool::new(true));
+        let incoming = spawn_reader(server, writer, Arc::clone(&awaiting_pong));
+
+        write_frame(&mut client, &Frame::Ping).unwrap();
+        assert!(matches!(read_frame(&mut client), Ok(Frame::Pong)));
+
+        write_frame(&mut client, &Frame::Pong).unwrap();
+        write_frame(&mut client, &Frame::Data(b"payload".to_vec())).unwrap();
+        assert_eq!(incoming.recv_timeout(Duration::from_secs(5)).unwrap(), b"payload");
+        // Frames are handled in order, so the pong was recorded before the data arrived
+        assert!(!awaiting_pong.load(Ordering::SeqCst));
+
+        drop(client);
+        assert!(incoming.recv_timeout(Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn keepalive_drops_a_peer_that_never_answers() {
+        let (server, mut client) = tcp_pair();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let interval = Duration::from_millis(50);
+        let started = Instant::now();
+        spawn_keepalive(Arc::new(Mutex::new(server)), Arc::new(AtomicBool::new(false)), interval, "[TEST]");
+
+        // One ping, then the connection is closed at the next tick instead of a second ping
+        assert!(matches!(read_frame(&mut client), Ok(Frame::Ping)));
+        assert_eq!(read_frame(&mut client).err().map(|e| e.kind()), Some(io::ErrorKind::UnexpectedEof));
+        assert!(started.elapsed() >= interval * 2);
+
+        assert!(Args::try_parse_from(["streamchat", "--keepalive", "0", "server"]).is_err());
+    }
+
+    #[test]
+    fn sealed_frames_only_open_at_their_direction_and_position() {
+        let key = mac_key(&SessionSecret::Seed(99), true);
+        let sealed = seal(b"cipher".to_vec(), &key, true, 10);
+        assert_eq!(sealed.len(), 6 + MAC_LEN);
+        assert_eq!(unseal(&sealed, &key, true, 10), Some(&b"cipher"[..]));
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert_eq!(unseal(&tampered, &key, true, 10), None);
+        // Replayed later in the stream, or reflected back to its sender
+        assert_eq!(unseal(&sealed, &key, true, 16), None);
+        assert_eq!(unseal(&sealed, &key, false, 10), None);
+        assert_eq!(unseal(&sealed, &mac_key(&SessionSecret::Seed(99), false), true, 10), None);
+        assert_eq!(unseal(&sealed[..MAC_LEN - 1], &key, true, 10), None);
+    }
+
+    #[test]
+    fn an_injected_frame_is_dropped_and_the_session_goes_on() {
+        let secret = fixed_secret();
+        let (server, mut client) = tcp_pair();
+        let mut sender = outgoing(server, &secret, true);
+        sender.send(MSG_TEXT, b"first").unwrap();
+        sender.send(MSG_TEXT, b"second").unwrap();
+
+        let mut keystream = Keystream::for_direction(&secret, true, false);
+        let key = mac_key(&secret, true);
+        let Ok(Frame::Data(genuine)) = read_frame(&mut client) else { panic!("expected a data frame") };
+        // A forged copy with one flipped bit arrives first, and is rejected without using keystream
+        let mut forged = genuine.clone();
+        forged[1] ^= 0x20;
+        assert_eq!(open_payload(&forged, &mut keystream, &key, true), None);
+        assert_eq!(keystream.position, 0);
+
+        let (_, _, plain) = open_payload(&genuine, &mut keystream, &key, true).unwrap();
+        assert_eq!(plain, b"\0first");
+        assert_eq!(receive(&mut client, &mut keystream, &key, true), (MSG_TEXT, b"second".to_vec()));
+    }
+
+    #[test]
+    fn downloads_stay_in_their_directory_and_never_overwrite() {
+        let dir = std::env::temp_dir().join(format!("streamchat_{}_downloads", std::process::id()));
+        let header = |size: u64, name: &str| [&size.to_le_bytes()[..], name.as_bytes()].concat();
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("passwd.1"), "already here").unwrap();
+        let first = Download::start(&dir, &header(3, "../../etc/passwd"), None).unwrap();
+        let second = Download::start(&dir, &header(3, "passwd"), None).unwrap();
+        let kept = fs::read_to_string(dir.join("passwd.1")).unwrap();
+        let unnamed = Download::start(&dir, &header(0, ".."), None).unwrap();
+        let truncated = Download::start(&dir, &[1, 2, 3], None).err();
+        let paths = [first.path.clone(), second.path.clone(), unnamed.path.clone()];
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(paths, [dir.join("passwd"), dir.join("passwd.2"), dir.join("download")]);
+        assert_eq!(kept, "already here");
+        assert_eq!(first.expected, 3);
+        assert_eq!(truncated.map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn names_travel_sealed_and_are_cut_at_char_boundaries() {
+        let secret = fixed_secret();
+        let (server, mut client) = tcp_pair();
+        let mut sender = outgoing(server, &secret, true);
+        let mut keystream = Keystream::for_direction(&secret, true, false);
+        let key = mac_key(&secret, true);
+        let mut round_trip = |name: &str| {
+            let (_, _, cipher) = sender.send(MSG_TEXT, name_bytes(name)).unwrap();
+            // What an eavesdropper sees is not the name
+            assert!(name.trim().is_empty() || cipher[1..] != *name_bytes(name));
+            let (kind, body) = receive(&mut client, &mut keystream, &key, true);
+            assert_eq!(kind, MSG_TEXT);
+            parse_name(&body)
+        };
+        assert_eq!(round_trip("alice").as_deref(), Some("alice"));
+        assert_eq!(round_trip("  bob \t").as_deref(), Some("bob"));
+        assert_eq!(round_trip("   "), None);
+
+        // 128 two-byte characters: the cut lands on a boundary below 255 bytes
+        let long = "é".repeat(128);
+        assert_eq!(name_bytes(&long).len(), 254);
+        assert_eq!(round_trip(&long).unwrap(), "é".repeat(127));
+
+        assert_eq!(parse_name(&[0xff, 0xfe]), None);
+    }
+
+    #[test]
+    fn explain_previews_handle_short_messages() {
+        assert_eq!(hex_preview(b""), "");
+        assert_eq!(hex_preview(b"a"), "61");
+        assert_eq!(hex_preview(b"abcd"), "61 62 63 64");
+        assert_eq!(hex_preview(b"abcde"), "61 62 63 64 ...");
+    }
+
+    #[test]
+    fn big_group_peers_derive_the_same_full_secret() {
+        assert_eq!(mod_exp(&BigUint::from(4u32), &BigUint::from(13u32), &BigUint::from(497u32)), BigUint::from(445u32));
+
+        let mut server = DHSession::with_private_key(DhGroup::new(false), BigUint::from(0xabcdef_u64) << 200);
+        let mut client = DHSession::with_private_key(DhGroup::new(false), BigUint::from(0x123457_u64) << 190);
+        // --explain shortens 2048-bit values
+        let shown = dh_hex(&server.public_key);
+        assert!(shown.len() < 64 && shown.contains("...") && shown.ends_with(" bits)"));
+        server.compute_shared_secret(&client.public_key).unwrap();
+        client.compute_shared_secret(&server.public_key).unwrap();
+
+        let (Some(SessionSecret::Full(ours)), Some(SessionSecret::Full(theirs))) = (server.session_secret(), client.session_secret()) else {
+            panic!("2048-bit group secrets are used whole");
+        };
+        assert_eq!(ours.len(), 256);
+        assert_eq!(ours, theirs);
+
+        let secret = SessionSecret::Full(ours);
+        assert_ne!(mac_key(&secret, true), mac_key(&secret, false));
+        assert_ne!(derive_key(b"streamchat key", &[0; 256], true), derive_key(b"streamchat key", &[1; 256], true));
+        let (_, to_client) = Keystream::for_direction(&secret, true, false).next_bytes(16);
+        let (_, to_server) = Keystream::for_direction(&secret, false, false).next_bytes(16);
+        assert_ne!(to_client, to_server);
+
+        // Degenerate peer keys (1, p - 1) are refused
+        assert!(server.compute_shared_secret(&BigUint::from(1u32)).is_err());
+        let p_minus_one = &server.group.p - BigUint::from(1u32);
+        assert!(server.compute_shared_secret(&p_minus_one).is_err());
+    }
 }
@@ -1,14 +1,19 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use rand::Rng;
-use std::collections::{BinaryHeap, HashMap};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::cmp::Ordering;
+use std::fmt::Write as _;
 use std::fs;
-use std::io::Write;
+use std::ops::RangeInclusive;
+use std::io::{IsTerminal, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
-#[command(name = "hexpath", version = "1.0", about = "Find min/max cost paths in hexadecimal grid")]
+#[command(name = "hexpath", version = "1.0", about = "Find min/max cost paths in hexadecimal grid", disable_help_flag = true)]
 struct Args {
-    /// Map file (hex values, space separated)
+    /// Map file (hex values, space separated; "-" reads stdin)
     map_file: Option<String>,
 
     /// Generate random map (e.g., 8x4, 16x12)
@@ -19,53 +24,321 @@ struct Args {
     #[arg(long)]
     output: Option<String>,
 
+    /// Start Search from cell R,C instead of the top-left corner
+    #[arg(long, value_name = "R,C")]
+    start: Option<String>,
+
+    /// End Search to cell R,C instead of the bottom-right corner
+    #[arg(long, value_name = "R,C")]
+    end: Option<String>,
+
+    /// Min cost Lowest generated cell value, hex byte (the start defaults to it)
+    #[arg(long, value_name = "HEX", default_value = "00", value_parser = parse_hex_byte)]
+    min_cost: u8,
+
+    /// Max cost Highest generated cell value, hex byte (the end defaults to it)
+    #[arg(long, value_name = "HEX", default_value = "FF", value_parser = parse_hex_byte)]
+    max_cost: u8,
+
+    /// Start value Pin the generated top-left cell to this hex byte [default: --min-cost]
+    #[arg(long)]
+    start_value: Option<String>,
+
+    /// End value Pin the generated bottom-right cell to this hex byte [default: --max-cost]
+    #[arg(long)]
+    end_value: Option<String>,
+
+    /// No pin Leave generated corners random unless a value is given
+    #[arg(long)]
+    no_pin: bool,
+
+    /// Max file size Refuse map files larger than N bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_FILE_SIZE)]
+    max_file_size: u64,
+
     /// Visualize Show colored map
     #[arg(long)]
     visualize: bool,
 
-    /// Both Show both min and max paths
+    /// Color Use ANSI colors: auto (only on a terminal), always or never
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Gradient steps Number of colors in the visualization gradient
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_GRADIENT_STEPS,
+          value_parser = clap::value_parser!(u16).range(1..=256))]
+    gradient_steps: u16,
+
+    /// Paths Show the K cheapest distinct paths that never revisit a cell
+    #[arg(long, value_name = "K", conflicts_with = "both",
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    paths: Option<usize>,
+
+    /// Both Show both the min-sum path and the max path (minimax: cheapest highest cell)
     #[arg(long)]
     both: bool,
 
+    /// Minimax Find the path whose most expensive cell is as cheap as possible
+    #[arg(long)]
+    minimax: bool,
+
     /// Animate ANIMATE pathfinding
     #[arg(long)]
     animate: bool,
 
+    /// FPS Frames per second of --animate
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    fps: u32,
+
+    /// Diagonal Also allow moves to the four diagonal neighbors (same cell cost)
+    #[arg(long)]
+    diagonal: bool,
+
+    /// Max depth Limit paths to at most N moves
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Algorithm Search algorithm: dijkstra, or astar (min-sum paths, same result)
+    #[arg(long, value_enum, default_value_t = Algorithm::Dijkstra)]
+    algorithm: Algorithm,
+
+    /// Reverse search Run Dijkstra from the end cell back to the start (same cost)
+    #[arg(long)]
+    reverse_search: bool,
+
+    /// Include start Add the start cell's value to the path cost (default: start is free)
+    #[arg(long)]
+    include_start: bool,
+
+    /// Distance CSV Save the shortest-distance grid as CSV
+    #[arg(long)]
+    distance_csv: Option<String>,
+
+    /// Reachable Report whether cell R,C can be reached and its minimum cost, then exit
+    #[arg(long, value_name = "R,C")]
+    reachable: Option<String>,
+
+    /// Cost histogram Count the cells reachable at each distance from the start, then exit
+    #[arg(long)]
+    cost_histogram: bool,
+
+    /// PNG Render the map and path(s) to a PNG image
+    #[arg(long, value_name = "FILE")]
+    png: Option<String>,
+
+    /// Cell size Side of each cell in the PNG, in pixels
+    #[arg(long, value_name = "PX", default_value_t = 16,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    cell_size: u32,
+
+    /// Border Frame the visualization with row/column indexes
+    #[arg(long)]
+    border: bool,
+
+    /// Debug neighbors Print the neighbors of cell R,C and exit
+    #[arg(long, hide = true, value_name = "R,C")]
+    debug_neighbors: Option<String>,
+
+    /// JSON Print the path as JSON instead of text
+    #[arg(long)]
+    json: bool,
+
+    /// Verbose JSON Add a per-cell cost breakdown to the JSON output (implies --json)
+    #[arg(long)]
+    verbose_json: bool,
+
+    /// Stats Report heap pops, relaxations and search time
+    #[arg(long)]
+    stats: bool,
+
+    /// Count only Print cost and length without the path listing
+    #[arg(long)]
+    count_only: bool,
+
     /// Help
     #[arg(short, long)]
     help: bool,
 }
 
+/// Default cap on map file size (256 MiB)
+const DEFAULT_MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Algorithm {
+    Dijkstra,
+    /// Dijkstra guided by a Manhattan-distance lower bound (same result)
+    Astar,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whether to emit ANSI escape codes: `auto` only colorizes when stdout is a terminal
+fn should_colorize(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stdout().is_terminal(),
+    }
+}
+
+/// The original seven-color rainbow
+const DEFAULT_GRADIENT_STEPS: u16 = 7;
+
+/// Rainbow waypoints as (r, g, b) coordinates in the xterm-256 6x6x6 color cube:
+/// red, orange, yellow, green, cyan, blue, purple
+const RAINBOW: [(f32, f32, f32); 7] = [
+    (5.0, 0.0, 0.0),
+    (5.0, 2.0, 0.0),
+    (5.0, 5.0, 0.0),
+    (0.0, 5.0, 0.0),
+    (0.0, 5.0, 5.0),
+    (0.0, 0.0, 5.0),
+    (2.0, 0.0, 5.0),
+];
+
+/// `steps` color cube coordinates evenly interpolated along the rainbow.
+/// Adjacent steps may share a color once `steps` exceeds what the cube can resolve.
+fn gradient_cube(steps: usize) -> Vec<[u8; 3]> {
+    (0..steps)
+        .map(|i| {
+            let t = if steps > 1 {
+                i as f32 * (RAINBOW.len() - 1) as f32 / (steps - 1) as f32
+            } else {
+                0.0
+            };
+            let lo = (t.floor() as usize).min(RAINBOW.len() - 2);
+            let frac = t - lo as f32;
+            let (a, b) = (RAINBOW[lo], RAINBOW[lo + 1]);
+            let mix = |x: f32, y: f32| (x + (y - x) * frac).round() as u8;
+            [mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2)]
+        })
+        .collect()
+}
+
+/// Foreground escape codes for the rainbow gradient
+fn gradient_palette(steps: usize) -> Vec<String> {
+    gradient_cube(steps)
+        .into_iter()
+        .map(|[r, g, b]| format!("\x1b[38;5;{}m", 16 + 36 * r + 6 * g + b))
+        .collect()
+}
+
+/// Channel intensities of the xterm-256 color cube levels 0-5
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Gradient entry for a byte: 0x00 maps to the first color and 0xFF to the last
+fn palette_index(cell: u8, len: usize) -> usize {
+    (cell as usize * len / 256).min(len - 1)
+}
+
+/// What a path's cost measures. Both objectives are minimized, so Dijkstra
+/// relaxes an edge only when the new cost is strictly lower for either one.
+///
+/// The "max path" shown by --both is the minimax path: among all routes, the one
+/// whose most expensive cell is as cheap as possible. Maximizing a sum instead
+/// would be a longest-path problem, which Dijkstra can't solve.
+#[derive(Clone, Copy, PartialEq)]
+enum Objective {
+    /// Sum of the cell values along the path
+    MinSum,
+    /// Largest single cell value along the path (bottleneck)
+    Minimax,
+}
+
+impl Objective {
+    fn extend(self, cost: u32, cell: u32) -> u32 {
+        match self {
+            Objective::MinSum => cost + cell,
+            Objective::Minimax => cost.max(cell),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Hash)]
 struct Pos(usize, usize);
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 struct State {
     cost: u32,
+    /// `cost` plus the A* estimate of what remains (equal to `cost` for Dijkstra)
+    est_total: u32,
     pos: Pos,
-    find_max: bool,
+    steps: usize,
 }
 
+// Both objectives minimize their cost, so the heap always pops the most promising state first.
+// Among equal estimates the costlier one is closer to the goal, so it goes first.
 impl Ord for State {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.find_max {
-            other.cost.cmp(&self.cost)
-        } else {
-            other.cost.cmp(&self.cost).reverse()
-        }
+        other.est_total.cmp(&self.est_total)
+            .then_with(|| self.cost.cmp(&other.cost))
     }
 }
 
+/// Work done by one search (--stats)
+#[derive(Clone, Copy, Default)]
+struct SearchStats {
+    /// States taken off the heap, stale ones included
+    popped: usize,
+    /// Successful edge relaxations (cost improvements pushed to the heap)
+    relaxations: usize,
+    elapsed: Duration,
+}
+
+/// Cells and moves a search must avoid (used by the k-shortest paths search)
+#[derive(Default)]
+struct Exclusions {
+    cells: HashSet<Pos>,
+    moves: HashSet<(Pos, Pos)>,
+}
+
+/// Everything about a path search except its endpoints
+#[derive(Clone, Copy)]
+struct SearchOptions {
+    objective: Objective,
+    algorithm: Algorithm,
+    /// Only consider routes of at most this many moves
+    max_depth: Option<usize>,
+    /// Charge the start cell's value too
+    include_start: bool,
+    /// Search from the end cell back to the start
+    reverse: bool,
+    /// Redraw the search state for every explored state
+    animation: Option<Animation>,
+}
+
+/// How --animate draws its frames
+#[derive(Clone, Copy)]
+struct Animation {
+    frame_delay: Duration,
+    color: bool,
+    /// Redraw in place; only with colors on a terminal, otherwise frames
+    /// are printed one after another, separated by a blank line
+    clear_screen: bool,
+}
+
 impl PartialOrd for State {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
+/// Map file token for an impassable cell
+const WALL_TOKEN: &str = "XX";
+
 struct Grid {
     cells: Vec<Vec<u8>>,
     width: usize,
     height: usize,
+    /// Impassable cells (`XX` in map files); their byte in `cells` is unused
+    walls: HashSet<Pos>,
+    /// Also move to the four diagonal cells (--diagonal)
+    diagonal: bool,
 }
 
 impl Grid {
@@ -74,33 +347,76 @@ impl Grid {
             cells: vec![vec![0; width]; height],
             width,
             height,
+            walls: HashSet::new(),
+            diagonal: false,
         }
     }
 
-    fn generate_random(width: usize, height: usize) -> Self {
+    /// Random grid with values in `range`; `start`/`end` pin the corner cells when given
+    fn generate_random(width: usize, height: usize, range: RangeInclusive<u8>, start: Option<u8>, end: Option<u8>) -> Self {
         let mut grid = Grid::new(width, height);
         let mut rng = rand::thread_rng();
         
         for row in &mut grid.cells {
             for cell in row {
-                *cell = rng.gen_range(0..=0xFF);
+                *cell = rng.gen_range(range.clone());
             }
         }
         
-        grid.cells[0][0] = 0x00;
-        grid.cells[height - 1][width - 1] = 0xFF;
+        if let Some(value) = start {
+            grid.cells[0][0] = value;
+        }
+        if let Some(value) = end {
+            grid.cells[height - 1][width - 1] = value;
+        }
         
         grid
     }
 
-    fn from_file(filename: &str) -> Result<Self, String> {
+    fn from_file(filename: &str, max_size: u64) -> Result<Self, String> {
+        let size = fs::metadata(filename)
+            .map_err(|e| format!("Cannot read file: {}", e))?
+            .len();
+        if size > max_size {
+            return Err(format!(
+                "Map file is {} bytes, over the {} byte limit (raise --max-file-size)",
+                size, max_size
+            ));
+        }
+
         let content = fs::read_to_string(filename)
             .map_err(|e| format!("Cannot read file: {}", e))?;
 
+        content.parse()
+    }
+
+    /// Map read from standard input, refused once it exceeds `max_size` bytes
+    fn from_stdin(max_size: u64) -> Result<Self, String> {
+        let mut content = String::new();
+        std::io::stdin()
+            .take(max_size + 1)
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Cannot read stdin: {}", e))?;
+        if content.len() as u64 > max_size {
+            return Err(format!(
+                "Map on stdin is over the {} byte limit (raise --max-file-size)",
+                max_size
+            ));
+        }
+
+        content.parse()
+    }
+}
+
+/// Map text: rows of space separated hex bytes, `XX` for walls
+impl std::str::FromStr for Grid {
+    type Err = String;
+
+    fn from_str(content: &str) -> Result<Self, String> {
         let mut grid = Grid::new(0, 0);
-        let mut rows = vec![];
+        let mut rows: Vec<Vec<u8>> = vec![];
 
-        for line in content.lines() {
+        for (line_idx, line) in content.lines().enumerate() {
             let line = line.trim();
             if line.is_empty() {
                 continue;
@@ -108,11 +424,29 @@ impl Grid {
 
             let mut row = vec![];
             for hex_str in line.split_whitespace() {
+                if hex_str.eq_ignore_ascii_case(WALL_TOKEN) {
+                    grid.walls.insert(Pos(rows.len(), row.len()));
+                    row.push(0);
+                    continue;
+                }
                 let byte = u8::from_str_radix(hex_str, 16)
                     .map_err(|_| format!("Invalid hex byte: {}", hex_str))?;
                 row.push(byte);
             }
 
+            // Every row must match the first one, or indexing reads the wrong cells
+            if let Some(first) = rows.first() {
+                if row.len() != first.len() {
+                    return Err(format!(
+                        "Row {} (line {}) has {} columns, expected {}",
+                        rows.len() + 1,
+                        line_idx + 1,
+                        row.len(),
+                        first.len()
+                    ));
+                }
+            }
+
             if !row.is_empty() {
                 rows.push(row);
             }
@@ -128,20 +462,39 @@ impl Grid {
 
         Ok(grid)
     }
+}
 
+impl Grid {
     fn save(&self, filename: &str) -> Result<(), String> {
         let mut file = fs::File::create(filename)
             .map_err(|e| format!("Cannot create file: {}", e))?;
 
-        for row in &self.cells {
-            let hex_row: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
-            writeln!(file, "{}", hex_row.join(" "))
+        for row in 0..self.height {
+            writeln!(file, "{}", self.row_text(row))
                 .map_err(|e| format!("Cannot write file: {}", e))?;
         }
 
         Ok(())
     }
 
+    /// Map file form of a row: hex bytes, `XX` for walls
+    fn row_text(&self, row: usize) -> String {
+        (0..self.width)
+            .map(|col| {
+                if self.is_wall(Pos(row, col)) {
+                    WALL_TOKEN.to_string()
+                } else {
+                    format!("{:02X}", self.cells[row][col])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn is_wall(&self, pos: Pos) -> bool {
+        self.walls.contains(&pos)
+    }
+
     fn get(&self, pos: Pos) -> Option<u8> {
         if pos.0 < self.height && pos.1 < self.width {
             Some(self.cells[pos.0][pos.1])
@@ -154,158 +507,605 @@ impl Grid {
         let mut neighbors = vec![];
         let (row, col) = (pos.0, pos.1);
 
-        let directions = [
-            (row.saturating_sub(1), col),
-            (row + 1, col),
-            (row, col.saturating_sub(1)),
-            (row, col + 1),
-        ];
+        // Signed offsets so that edge cells don't clamp back onto themselves
+        const ORTHOGONAL: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+        const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let diagonal: &[(isize, isize)] = if self.diagonal { &DIAGONAL } else { &[] };
 
-        for (r, c) in &directions {
-            if *r < self.height && *c < self.width {
-                neighbors.push(Pos(*r, *c));
+        for &(dr, dc) in ORTHOGONAL.iter().chain(diagonal) {
+            if let (Some(r), Some(c)) = (row.checked_add_signed(dr), col.checked_add_signed(dc)) {
+                if r < self.height && c < self.width && !self.is_wall(Pos(r, c)) {
+                    neighbors.push(Pos(r, c));
+                }
             }
         }
 
         neighbors
     }
 
-    fn dijkstra(&self, find_max: bool, animate: bool) -> (Vec<Pos>, u32) {
-        let start = Pos(0, 0);
-        let end = Pos(self.height - 1, self.width - 1);
+    /// Dijkstra from `start` to `end` (corner to corner by default).
+    /// The start cell costs nothing unless `include_start` is set; every other
+    /// cell on the path adds its byte value.
+    /// With `max_depth`, the search runs on (cell, moves) states so that only
+    /// routes of at most that many moves are considered.
+    /// With `reverse`, the search starts at the end cell; costs are charged the
+    /// same way so the result matches the forward search, and the path is still
+    /// returned start-to-end.
+    /// With A* (min-sum only), states are ordered by cost plus a lower bound on
+    /// the remaining cost, which keeps the result identical while exploring less.
+    fn dijkstra(&self, endpoints: (Pos, Pos), options: SearchOptions) -> (Vec<Pos>, u32, SearchStats) {
+        self.search(endpoints, options, &Exclusions::default())
+    }
 
-        let mut dist: HashMap<Pos, u32> = HashMap::new();
-        let mut parent: HashMap<Pos, Pos> = HashMap::new();
+    /// `dijkstra` that never enters `excluded.cells` nor takes `excluded.moves`
+    fn search(&self, (start, end): (Pos, Pos), options: SearchOptions, excluded: &Exclusions) -> (Vec<Pos>, u32, SearchStats) {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let SearchOptions { objective, algorithm, max_depth, include_start, reverse, animation } = options;
+        let (source, target) = if reverse { (end, start) } else { (start, end) };
+
+        // Entering a cell costs its value, except the start cell unless included
+        let cell_cost = |pos: Pos| {
+            if pos == start && !include_start {
+                0
+            } else {
+                self.get(pos).unwrap_or(0) as u32
+            }
+        };
+
+        // Cheapest possible move: any path of d moves costs at least d times this.
+        // The source is never worth re-entering, so it is left out.
+        let cheapest_move = (0..self.height)
+            .flat_map(|r| (0..self.width).map(move |c| Pos(r, c)))
+            .filter(|&pos| pos != source && !self.is_wall(pos))
+            .map(cell_cost)
+            .min()
+            .unwrap_or(0);
+        let heuristic = |pos: Pos| -> u32 {
+            if algorithm != Algorithm::Astar || objective != Objective::MinSum {
+                return 0;
+            }
+            let (dr, dc) = (pos.0.abs_diff(target.0), pos.1.abs_diff(target.1));
+            // Diagonal moves cover a row and a column at once
+            let moves = if self.diagonal { dr.max(dc) } else { dr + dc };
+            moves as u32 * cheapest_move
+        };
+
+        // Without a depth limit every state collapses to depth 0 (plain Dijkstra)
+        let depth_key = |steps: usize| if max_depth.is_some() { steps } else { 0 };
+
+        let mut dist: HashMap<(Pos, usize), u32> = HashMap::new();
+        let mut parent: HashMap<(Pos, usize), (Pos, usize)> = HashMap::new();
         let mut heap = BinaryHeap::new();
 
-        dist.insert(start, 0);
+        let start_cost = cell_cost(source);
+
+        dist.insert((source, 0), start_cost);
         heap.push(State {
-            cost: 0,
-            pos: start,
-            find_max,
+            cost: start_cost,
+            est_total: start_cost + heuristic(source),
+            pos: source,
+            steps: 0,
         });
 
-        let mut step = 0;
-
-        while let Some(State { cost, pos, find_max: _ }) = heap.pop() {
-            if animate {
-                println!("[>] [Step {}] Exploring ({},{}) - cost: {}", step, pos.0, pos.1, cost);
-                step += 1;
+        // Cells already expanded, only tracked for the animation
+        let mut visited = HashSet::new();
+
+        while let Some(State { cost, pos, steps, .. }) = heap.pop() {
+            if let Some(animation) = animation {
+                let frontier: HashSet<Pos> = heap.iter().map(|state| state.pos).collect();
+                print!("{}", self.render_search_frame(pos, &visited, &frontier, animation));
+                println!("[>] [Step {}] Exploring ({},{}) - cost: {}", stats.popped, pos.0, pos.1, cost);
+                std::io::stdout().flush().ok();
+                thread::sleep(animation.frame_delay);
+                visited.insert(pos);
             }
+            stats.popped += 1;
+
+            let key = (pos, depth_key(steps));
 
-            if pos == end {
+            if pos == target {
                 let mut path = vec![pos];
-                let mut current = pos;
+                let mut current = key;
 
                 while let Some(&prev) = parent.get(&current) {
-                    path.push(prev);
+                    path.push(prev.0);
                     current = prev;
                 }
 
-                path.reverse();
-                return (path, cost);
+                // Parents lead back to the source: the start, unless searching in reverse
+                if !reverse {
+                    path.reverse();
+                }
+                stats.elapsed = started.elapsed();
+                return (path, cost, stats);
             }
 
-            if let Some(&d) = dist.get(&pos) {
+            if let Some(&d) = dist.get(&key) {
                 if cost > d {
                     continue;
                 }
             }
 
+            if max_depth.is_some_and(|limit| steps >= limit) {
+                continue;
+            }
+
             for next_pos in self.neighbors(pos) {
-                if let Some(next_cost_byte) = self.get(next_pos) {
-                    let next_cost_byte = next_cost_byte as u32;
-                    let new_cost = if find_max {
-                        cost.max(next_cost_byte)
-                    } else {
-                        cost + next_cost_byte
-                    };
+                if excluded.cells.contains(&next_pos) || excluded.moves.contains(&(pos, next_pos)) {
+                    continue;
+                }
+                if self.get(next_pos).is_some() {
+                    let new_cost = objective.extend(cost, cell_cost(next_pos));
+                    let next_key = (next_pos, depth_key(steps + 1));
 
-                    let should_update = if let Some(&curr_dist) = dist.get(&next_pos) {
-                        if find_max {
-                            new_cost < curr_dist
-                        } else {
-                            new_cost < curr_dist
-                        }
+                    let should_update = if let Some(&curr_dist) = dist.get(&next_key) {
+                        new_cost < curr_dist
                     } else {
                         true
                     };
 
                     if should_update {
-                        dist.insert(next_pos, new_cost);
-                        parent.insert(next_pos, pos);
+                        stats.relaxations += 1;
+                        dist.insert(next_key, new_cost);
+                        parent.insert(next_key, key);
                         heap.push(State {
                             cost: new_cost,
+                            est_total: new_cost + heuristic(next_pos),
                             pos: next_pos,
-                            find_max,
+                            steps: steps + 1,
                         });
                     }
                 }
             }
         }
 
-        (vec![], 0)
+        stats.elapsed = started.elapsed();
+        (vec![], 0, stats)
     }
 
-    fn print_map(&self) {
-        for row in &self.cells {
-            let hex_row: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
-            println!("{}", hex_row.join(" "));
+    /// Up to `k` cheapest simple paths, cheapest first (Yen's algorithm).
+    /// Each candidate branches off an accepted path at a spur cell: the route up
+    /// to the spur is kept, the moves already taken from it by accepted paths
+    /// sharing that route are forbidden, and so are the earlier route cells,
+    /// so no path revisits a cell.
+    fn k_shortest_paths(&self, (start, end): (Pos, Pos), options: SearchOptions, k: usize) -> Vec<(Vec<Pos>, u32)> {
+        let options = SearchOptions { reverse: false, animation: None, ..options };
+        let (first, cost, _) = self.dijkstra((start, end), options);
+        if first.is_empty() || k == 0 {
+            return vec![];
         }
+
+        let mut accepted = vec![(first, cost)];
+        let mut candidates: Vec<(Vec<Pos>, u32)> = vec![];
+
+        while accepted.len() < k {
+            let previous = accepted.last().unwrap().0.clone();
+
+            for i in 0..previous.len() - 1 {
+                let spur = previous[i];
+                let root = &previous[..=i];
+
+                let mut excluded = Exclusions::default();
+                excluded.cells.extend(&root[..i]);
+                for (path, _) in &accepted {
+                    if path.len() > i + 1 && path[..=i] == *root {
+                        excluded.moves.insert((path[i], path[i + 1]));
+                    }
+                }
+
+                let spur_options = SearchOptions {
+                    max_depth: options.max_depth.map(|d| d.saturating_sub(i)),
+                    ..options
+                };
+                let (spur_path, _, _) = self.search((spur, end), spur_options, &excluded);
+                if spur_path.is_empty() {
+                    continue;
+                }
+
+                let mut path = root[..i].to_vec();
+                path.extend(spur_path);
+                let in_accepted = accepted.iter().any(|(p, _)| *p == path);
+                if !in_accepted && !candidates.iter().any(|(p, _)| *p == path) {
+                    let cost = cost_breakdown(self, &path, options.objective, options.include_start)
+                        .last()
+                        .map_or(0, |&(_, _, total)| total);
+                    candidates.push((path, cost));
+                }
+            }
+
+            // Cheapest candidate next, shorter first on ties; stop when none are left
+            let Some(best) = (0..candidates.len())
+                .min_by_key(|&c| (candidates[c].1, candidates[c].0.len()))
+            else {
+                break;
+            };
+            accepted.push(candidates.swap_remove(best));
+        }
+
+        accepted
+    }
+
+    /// Min-sum distance from the start to every cell (full Dijkstra, no early exit).
+    /// Unreachable cells are `None`.
+    fn distance_field(&self, start: Pos, include_start: bool) -> Vec<Vec<Option<u32>>> {
+        self.settle_distances(start, include_start, None)
     }
 
-    fn visualize(&self, min_path: &[Pos], max_path: &[Pos]) {
-        println!("HEXADECIMAL GRID (rainbow gradient):");
-        let colors = vec![
-            "\x1b[38;5;196m", // Red
-            "\x1b[38;5;208m", // Orange
-            "\x1b[38;5;226m", // Yellow
-            "\x1b[38;5;46m",  // Green
-            "\x1b[38;5;51m",  // Cyan
-            "\x1b[38;5;21m",  // Blue
-            "\x1b[38;5;93m",  // Purple
-        ];
+    /// Min-sum cost from the start to `target`, or `None` if it can't be reached.
+    /// Stops as soon as the target is settled; no path is reconstructed.
+    fn probe(&self, start: Pos, target: Pos, include_start: bool) -> Option<u32> {
+        self.settle_distances(start, include_start, Some(target))[target.0][target.1]
+    }
 
-        println!();
-        for (row_idx, row) in self.cells.iter().enumerate() {
-            for (col_idx, &cell) in row.iter().enumerate() {
-                let pos = Pos(row_idx, col_idx);
-                let color_idx = (cell as usize / 256) * colors.len() / 256;
-                let color = colors[color_idx];
+    /// Dijkstra from the start, stopping early once `stop_at` is popped
+    /// (its distance is final then; other cells may be tentative)
+    fn settle_distances(&self, start: Pos, include_start: bool, stop_at: Option<Pos>) -> Vec<Vec<Option<u32>>> {
+        let mut dist = vec![vec![None; self.width]; self.height];
+        let mut heap = BinaryHeap::new();
 
-                if min_path.contains(&pos) {
-                    print!("{} {:02X}\x1b[0m", "\x1b[37m", cell); // White for min
-                } else if max_path.contains(&pos) {
-                    print!("{} {:02X}\x1b[0m", "\x1b[91m", cell); // Bright red for max
+        let start_cost = if include_start {
+            self.get(start).unwrap_or(0) as u32
+        } else {
+            0
+        };
+
+        dist[start.0][start.1] = Some(start_cost);
+        heap.push(State {
+            cost: start_cost,
+            est_total: start_cost,
+            pos: start,
+            steps: 0,
+        });
+
+        while let Some(State { cost, pos, steps, .. }) = heap.pop() {
+            if dist[pos.0][pos.1].is_some_and(|d| cost > d) {
+                continue;
+            }
+
+            if stop_at == Some(pos) {
+                break;
+            }
+
+            for next_pos in self.neighbors(pos) {
+                if let Some(next_cost_byte) = self.get(next_pos) {
+                    let new_cost = cost + next_cost_byte as u32;
+                    if dist[next_pos.0][next_pos.1].is_none_or(|d| new_cost < d) {
+                        dist[next_pos.0][next_pos.1] = Some(new_cost);
+                        heap.push(State {
+                            cost: new_cost,
+                            est_total: new_cost,
+                            pos: next_pos,
+                            steps: steps + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        dist
+    }
+
+    /// Number of cells at each distinct min-sum distance from the start
+    fn cost_histogram(&self, start: Pos, include_start: bool) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+        for cost in self.distance_field(start, include_start).into_iter().flatten().flatten() {
+            *histogram.entry(cost).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    fn save_distance_csv(&self, filename: &str, start: Pos, include_start: bool) -> Result<(), String> {
+        let mut file = fs::File::create(filename)
+            .map_err(|e| format!("Cannot create file: {}", e))?;
+
+        for row in self.distance_field(start, include_start) {
+            // Unreachable cells are left empty
+            let csv_row: Vec<String> = row
+                .iter()
+                .map(|d| d.map(|d| d.to_string()).unwrap_or_default())
+                .collect();
+            writeln!(file, "{}", csv_row.join(","))
+                .map_err(|e| format!("Cannot write file: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the map as a PNG: one `cell_size` square per cell in the `visualize`
+    /// gradient, the min path in white, the max path in red and walls in gray
+    fn render_png(&self, filename: &str, min_path: &[Pos], max_path: &[Pos], cell_size: u32, gradient_steps: usize) -> Result<(), String> {
+        let palette: Vec<image::Rgb<u8>> = gradient_cube(gradient_steps)
+            .into_iter()
+            .map(|cube| image::Rgb(cube.map(|level| CUBE_LEVELS[level as usize])))
+            .collect();
+
+        let width = (self.width as u32).checked_mul(cell_size);
+        let height = (self.height as u32).checked_mul(cell_size);
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err("Image too large, lower --cell-size".to_string());
+        };
+
+        let image = image::RgbImage::from_fn(width, height, |x, y| {
+            let pos = Pos((y / cell_size) as usize, (x / cell_size) as usize);
+            if self.is_wall(pos) {
+                image::Rgb([88, 88, 88])
+            } else if min_path.contains(&pos) {
+                image::Rgb([255, 255, 255])
+            } else if max_path.contains(&pos) {
+                image::Rgb([255, 0, 0])
+            } else {
+                palette[palette_index(self.cells[pos.0][pos.1], palette.len())]
+            }
+        });
+
+        image.save_with_format(filename, image::ImageFormat::Png)
+            .map_err(|e| format!("Cannot write {}: {}", filename, e))
+    }
+
+    /// One --animate frame: clears the screen, then draws the grid with visited
+    /// cells dimmed, queued (frontier) cells marked and the current cell highlighted
+    fn render_search_frame(&self, current: Pos, visited: &HashSet<Pos>, frontier: &HashSet<Pos>, animation: Animation) -> String {
+        let color = animation.color;
+        let mut frame = if animation.clear_screen {
+            String::from("\x1b[2J\x1b[H")
+        } else {
+            String::from("\n")
+        };
+        if !color {
+            frame.push_str("(@ = current, ? = frontier, . = visited)\n");
+        }
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let pos = Pos(row, col);
+                let text = if self.is_wall(pos) {
+                    "##".to_string()
+                } else {
+                    format!("{:02X}", self.cells[row][col])
+                };
+                let (style, marker) = if pos == current {
+                    ("\x1b[7m", '@') // Reverse video
+                } else if frontier.contains(&pos) {
+                    ("\x1b[1;33m", '?') // Bold yellow
+                } else if visited.contains(&pos) {
+                    ("\x1b[2m", '.') // Dim
                 } else {
-                    print!("{}{:02X}\x1b[0m ", color, cell);
+                    ("", ' ')
+                };
+                if !color {
+                    frame.push_str(&format!("{}{}", text, marker));
+                } else if style.is_empty() {
+                    frame.push_str(&format!("{} ", text));
+                } else {
+                    frame.push_str(&format!("{}{}\x1b[0m ", style, text));
                 }
             }
-            println!();
+            frame.push('\n');
+        }
+        frame
+    }
+
+    /// Colored (or `*`/`+` marked) map; with `border`, framed with row/column indexes
+    fn visualize(&self, min_path: &[Pos], max_path: &[Pos], color: bool, border: bool, gradient_steps: usize) {
+        print!("{}", self.render_map(min_path, max_path, color, border, gradient_steps));
+    }
+
+    fn render_map(&self, min_path: &[Pos], max_path: &[Pos], color: bool, border: bool, gradient_steps: usize) -> String {
+        let mut out = String::new();
+        if color {
+            out.push_str("HEXADECIMAL GRID (rainbow gradient):\n");
+        } else {
+            out.push_str("HEXADECIMAL GRID (* = min path, + = max path, ## = wall):\n");
         }
+        let colors = gradient_palette(gradient_steps);
+
+        // Every cell renders 3 columns wide
+        let render_cell = |pos: Pos, cell: u8| -> String {
+            if self.is_wall(pos) {
+                return if color {
+                    "\x1b[48;5;240m##\x1b[0m ".to_string() // Gray block
+                } else {
+                    "## ".to_string()
+                };
+            }
+
+            if !color {
+                let marker = if min_path.contains(&pos) {
+                    '*'
+                } else if max_path.contains(&pos) {
+                    '+'
+                } else {
+                    ' '
+                };
+                return format!("{:02X}{}", cell, marker);
+            }
+
+            let color = &colors[palette_index(cell, colors.len())];
+
+            if min_path.contains(&pos) {
+                format!("{} {:02X}\x1b[0m", "\x1b[37m", cell) // White for min
+            } else if max_path.contains(&pos) {
+                format!("{} {:02X}\x1b[0m", "\x1b[91m", cell) // Bright red for max
+            } else {
+                format!("{}{:02X}\x1b[0m ", color, cell)
+            }
+        };
+
+        let label_width = self.height.saturating_sub(1).to_string().len();
+        let frame = "─".repeat(self.width * 3 + 1);
+
+        out.push('\n');
+        if border {
+            let header: String = (0..self.width).map(|c| format!("{:<3}", c)).collect();
+            writeln!(out, "{:w$}   {}", "", header.trim_end(), w = label_width).unwrap();
+            writeln!(out, "{:w$} ┌{}┐", "", frame, w = label_width).unwrap();
+        }
+        for (row_idx, row) in self.cells.iter().enumerate() {
+            if border {
+                write!(out, "{:>w$} │ ", row_idx, w = label_width).unwrap();
+            }
+            for (col_idx, &cell) in row.iter().enumerate() {
+                out.push_str(&render_cell(Pos(row_idx, col_idx), cell));
+            }
+            if border {
+                out.push('│');
+            }
+            out.push('\n');
+        }
+        if border {
+            writeln!(out, "{:w$} └{}┘", "", frame, w = label_width).unwrap();
+        }
+        out
     }
 }
 
-fn print_path_info(label: &str, path: &[Pos], cost: u32) {
-    println!();
-    println!("{} COST PATH:", label);
-    println!("==================");
-    println!("Total cost: 0x{:X} ({} decimal)", cost, cost);
-    println!("Path length: {} steps", path.len());
-    println!("Path:");
-    println!("{}", path.iter()
+/// Each path cell with its byte value and the cost accumulated up to and including it
+fn cost_breakdown(grid: &Grid, path: &[Pos], objective: Objective, include_start: bool) -> Vec<(Pos, u8, u32)> {
+    let mut total = 0;
+    path.iter()
+        .enumerate()
+        .map(|(i, &pos)| {
+            let value = grid.get(pos).unwrap_or(0);
+            if i > 0 || include_start {
+                total = objective.extend(total, value as u32);
+            }
+            (pos, value, total)
+        })
+        .collect()
+}
+
+/// `{"cost", "length", "path": [[row, col], ...]}`; no path gives `"cost": null, "path": []`
+fn path_json(grid: &Grid, path: &[Pos], cost: u32, objective: Objective, include_start: bool, verbose: bool) -> Value {
+    let cost = if path.is_empty() { None } else { Some(cost) };
+    let mut result = json!({
+        "cost": cost,
+        "length": path.len(),
+        "path": path.iter().map(|p| [p.0, p.1]).collect::<Vec<_>>(),
+    });
+
+    if verbose {
+        result["breakdown"] = cost_breakdown(grid, path, objective, include_start)
+            .into_iter()
+            .map(|(pos, value, total)| json!({ "pos": [pos.0, pos.1], "value": value, "cumulative": total }))
+            .collect();
+    }
+
+    result
+}
+
+/// --cost-histogram levels (ascending cost) with the reachable and open cell counts
+fn histogram_json(grid: &Grid, start: Pos, include_start: bool) -> Value {
+    let histogram = grid.cost_histogram(start, include_start);
+    let levels: Vec<Value> = histogram
+        .iter()
+        .map(|(cost, cells)| json!({ "cost": cost, "cells": cells }))
+        .collect();
+    json!({
+        "levels": levels,
+        "reachable": histogram.values().sum::<usize>(),
+        "open_cells": grid.width * grid.height - grid.walls.len(),
+    })
+}
+
+/// Parse "R,C" into a position (row, column)
+fn parse_pos(spec: &str) -> Result<Pos, String> {
+    let (row, col) = spec
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid position: {} (expected R,C)", spec))?;
+    let row = row.trim().parse().map_err(|_| format!("Invalid row: {}", row))?;
+    let col = col.trim().parse().map_err(|_| format!("Invalid column: {}", col))?;
+    Ok(Pos(row, col))
+}
+
+/// Parse "R,C" and check that the cell exists in the grid
+fn parse_cell(spec: &str, grid: &Grid) -> Result<Pos, String> {
+    let pos = parse_pos(spec)?;
+    if grid.get(pos).is_none() {
+        return Err(format!("({},{}) is outside the {}x{} grid", pos.0, pos.1, grid.width, grid.height));
+    }
+    Ok(pos)
+}
+
+/// `--start`/`--end` cells, defaulting to the top-left and bottom-right corners
+fn search_endpoints(start: Option<&str>, end: Option<&str>, grid: &Grid) -> Result<(Pos, Pos), String> {
+    let start = match start {
+        Some(spec) => parse_cell(spec, grid)?,
+        None => Pos(0, 0),
+    };
+    let end = match end {
+        Some(spec) => parse_cell(spec, grid)?,
+        None => Pos(grid.height - 1, grid.width - 1),
+    };
+    for (name, pos) in [("Start", start), ("End", end)] {
+        if grid.is_wall(pos) {
+            return Err(format!("{} cell ({},{}) is a wall", name, pos.0, pos.1));
+        }
+    }
+    Ok((start, end))
+}
+
+/// Parse a byte value written in hex, with or without a 0x prefix
+fn parse_hex_byte(value: &str) -> Result<u8, String> {
+    let digits = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    u8::from_str_radix(digits, 16)
+        .map_err(|_| format!("Invalid byte value: {} (expected 00-FF)", value))
+}
+
+/// Value a generated corner is pinned to: the given byte, else `default` unless unpinned
+fn corner_pin(value: Option<&str>, default: u8, no_pin: bool) -> Result<Option<u8>, String> {
+    match value {
+        Some(v) => parse_hex_byte(v).map(Some),
+        None if no_pin => Ok(None),
+        None => Ok(Some(default)),
+    }
+}
+
+/// Cost, length and (unless `count_only`) the path with its step-by-step costs
+fn path_info(grid: &Grid, label: &str, path: &[Pos], cost: u32, objective: Objective, include_start: bool, count_only: bool) -> String {
+    let mut out = String::new();
+    writeln!(out).unwrap();
+    writeln!(out, "{} COST PATH:", label).unwrap();
+    writeln!(out, "==================").unwrap();
+    writeln!(out, "Total cost: 0x{:X} ({} decimal)", cost, cost).unwrap();
+    writeln!(out, "Path length: {} steps", path.len()).unwrap();
+
+    if count_only {
+        return out;
+    }
+
+    writeln!(out, "Path:").unwrap();
+    writeln!(out, "{}", path.iter()
         .map(|p| format!("({},{})", p.0, p.1))
         .collect::<Vec<_>>()
-        .join("-"));
+        .join("-")).unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "Step-by-step costs:").unwrap();
+    let mut previous = 0;
+    for (i, (pos, value, total)) in cost_breakdown(grid, path, objective, include_start).into_iter().enumerate() {
+        // Cell value, what it added to the cost, and the running cost
+        let added = if i == 0 && !include_start { 0 } else { total - previous };
+        writeln!(out, "- 0x{:02X} ({},{}) +{} = {}", value, pos.0, pos.1, added, total).unwrap();
+        previous = total;
+    }
+    writeln!(out, "Total: 0x{:X} ({})", cost, cost).unwrap();
+    out
+}
 
+fn print_stats(stats: &SearchStats) {
     println!();
-    println!("Step-by-step costs:");
-    for (i, pos) in path.iter().enumerate() {
-        let step_num = i;
-        println!("- 0x{:02X} ({},{}) +{}", pos.1 * 16 + pos.0, pos.0, pos.1, 
-                 if i == 0 { 0 } else { 1 });
-    }
-    println!("Total: 0x{:X} ({})", cost, cost);
+    println!("Search stats:");
+    println!("Heap pops: {}", stats.popped);
+    println!("Relaxations: {}", stats.relaxations);
+    println!("Time: {:.3} ms", stats.elapsed.as_secs_f64() * 1000.0);
+}
+
+fn stats_json(stats: &SearchStats) -> Value {
+    json!({
+        "popped": stats.popped,
+        "relaxations": stats.relaxations,
+        "elapsed_ms": stats.elapsed.as_secs_f64() * 1000.0,
+    })
 }
 
 fn main() {
@@ -317,19 +1117,57 @@ fn main() {
         println!("Find min/max cost paths in hexadecimal grid");
         println!();
         println!("Arguments:");
-        println!("  Map file (hex values, space separated)");
+        println!("  Map file (hex values, space separated; - or piped input reads stdin)");
         println!();
         println!("Options:");
         println!("  --generate WxH          Generate random map (e.g., 8x4, 10x10)");
         println!("  --output FILE           Save generated map to file");
+        println!("  --start R,C             Start cell [default: top-left corner]");
+        println!("  --end R,C               End cell [default: bottom-right corner]");
+        println!("  --min-cost HEX          Lowest generated value [default: 00]");
+        println!("  --max-cost HEX          Highest generated value [default: FF]");
+        println!("  --start-value HEX       Generated top-left value [default: --min-cost]");
+        println!("  --end-value HEX         Generated bottom-right value [default: --max-cost]");
+        println!("  --no-pin                Leave generated corners random");
+        println!("  --max-file-size N       Refuse map files over N bytes [default: 268435456]");
         println!("  --visualize             Show colored map");
-        println!("  --both                  Show both min and max paths");
+        println!("  --color WHEN            Colorize output: auto, always, never [default: auto]");
+        println!("  --gradient-steps N      Colors in the visualization gradient (1-256) [default: 7]");
+        println!("  --png FILE              Render the map and path(s) to a PNG image");
+        println!("  --cell-size PX          PNG pixels per cell [default: 16]");
+        println!("  --border                Frame the visualization with row/column indexes");
+        println!("  --paths K               Show the K cheapest distinct paths");
+        println!("  --both                  Show both min and max (minimax) paths");
+        println!("  --minimax               Minimize the most expensive cell on the path");
         println!("  --animate               Animate pathfinding");
+        println!("  --fps N                 Animation frames per second [default: 10]");
+        println!("  --diagonal              Allow diagonal moves");
+        println!("  --max-depth N           Limit paths to at most N moves");
+        println!("  --algorithm ALGO        Search algorithm: dijkstra, astar [default: dijkstra]");
+        println!("  --reverse-search        Search from the end cell back to the start");
+        println!("  --include-start         Add the start cell's value to the cost (default: start is free)");
+        println!("  --distance-csv FILE     Save the shortest-distance grid as CSV");
+        println!("  --reachable R,C         Report whether R,C is reachable and at what cost");
+        println!("  --cost-histogram        Count reachable cells at each cost from the start");
+        println!("  --json                  Print the path as JSON");
+        println!("  --verbose-json          JSON with a per-cell cost breakdown");
+        println!("  --stats                 Report heap pops, relaxations and search time");
+        println!("  --count-only            Print cost and length without the path listing");
         println!("  -h, --help              Print help");
         return;
     }
 
-    let mut grid = if let Some(gen_spec) = args.generate {
+    let json_output = args.json || args.verbose_json;
+    // Under --json only the JSON document goes to stdout, status lines go to stderr
+    let status = |line: &str| {
+        if json_output {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    let mut grid = if let Some(gen_spec) = &args.generate {
         let parts: Vec<&str> = gen_spec.split('x').collect();
         if parts.len() != 2 {
             eprintln!("Error: Invalid generate format. Use WxH (e.g., 8x4)");
@@ -339,24 +1177,46 @@ fn main() {
         let width: usize = parts[0].parse().unwrap_or(8);
         let height: usize = parts[1].parse().unwrap_or(4);
 
-        println!("Generating {}x{} hexadecimal grid...", width, height);
-        let generated = Grid::generate_random(width, height);
+        if args.min_cost > args.max_cost {
+            eprintln!("Error: --min-cost {:02X} is above --max-cost {:02X}", args.min_cost, args.max_cost);
+            return;
+        }
 
-        if let Some(output_file) = args.output {
-            if let Err(e) = generated.save(&output_file) {
+        let pins = corner_pin(args.start_value.as_deref(), args.min_cost, args.no_pin)
+            .and_then(|start| Ok((start, corner_pin(args.end_value.as_deref(), args.max_cost, args.no_pin)?)));
+        let (start_pin, end_pin) = match pins {
+            Ok(pins) => pins,
+            Err(e) => {
                 eprintln!("Error: {}", e);
                 return;
             }
-            println!("Map saved to: {}", output_file);
+        };
+
+        status(&format!("Generating {}x{} hexadecimal grid...", width, height));
+        let generated = Grid::generate_random(width, height, args.min_cost..=args.max_cost, start_pin, end_pin);
+
+        if let Some(output_file) = &args.output {
+            if let Err(e) = generated.save(output_file) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+            status(&format!("Map saved to: {}", output_file));
         }
 
-        println!();
-        println!("Generated map:");
-        generated.print_map();
+        status("");
+        status("Generated map:");
+        for row in 0..generated.height {
+            status(&generated.row_text(row));
+        }
 
         generated
-    } else if let Some(map_file) = args.map_file {
-        match Grid::from_file(&map_file) {
+    } else if args.map_file.is_some() || !std::io::stdin().is_terminal() {
+        // "-", or no map file with data piped in, reads the map from stdin
+        let loaded = match args.map_file.as_deref() {
+            Some(map_file) if map_file != "-" => Grid::from_file(map_file, args.max_file_size),
+            _ => Grid::from_stdin(args.max_file_size),
+        };
+        match loaded {
             Ok(g) => g,
             Err(e) => {
                 eprintln!("Error: {}", e);
@@ -367,41 +1227,632 @@ fn main() {
         eprintln!("Error: Provide map file or use --generate");
         return;
     };
+    grid.diagonal = args.diagonal;
 
-    println!();
-    println!("Analyzing hexadecimal grid...");
-    println!("Grid size: {}x{}", grid.width, grid.height);
-    println!("Start: (0,0) = 0x00");
-    println!("End: ({},{}) = 0x{:02X}", grid.height - 1, grid.width - 1, 
-             grid.cells[grid.height - 1][grid.width - 1]);
+    let (start, end) = match search_endpoints(args.start.as_deref(), args.end.as_deref(), &grid) {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    if let Some(spec) = &args.debug_neighbors {
+        let pos = match parse_cell(spec, &grid) {
+            Ok(pos) => pos,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        };
+
+        let neighbors = grid.neighbors(pos);
+        println!();
+        println!("Neighbors of ({},{}) = 0x{:02X}:", pos.0, pos.1, grid.cells[pos.0][pos.1]);
+        for n in &neighbors {
+            println!("- ({},{}) = 0x{:02X}", n.0, n.1, grid.cells[n.0][n.1]);
+        }
+        println!("Total: {} neighbors", neighbors.len());
+        return;
+    }
+
+    if !json_output {
+        println!();
+        println!("Analyzing hexadecimal grid...");
+        println!("Grid size: {}x{}", grid.width, grid.height);
+        println!("Start: ({},{}) = 0x{:02X}", start.0, start.1, grid.cells[start.0][start.1]);
+        println!("End: ({},{}) = 0x{:02X}", end.0, end.1, grid.cells[end.0][end.1]);
+    }
+
+    if let Some(csv_file) = &args.distance_csv {
+        if let Err(e) = grid.save_distance_csv(csv_file, start, args.include_start) {
+            eprintln!("Error: {}", e);
+            return;
+        }
+        status(&format!("Distance field saved to: {}", csv_file));
+    }
+
+    if let Some(spec) = &args.reachable {
+        let target = match parse_cell(spec, &grid) {
+            Ok(pos) => pos,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        };
+
+        let cost = grid.probe(start, target, args.include_start);
+        if json_output {
+            println!("{}", json!({ "reachable": cost.is_some(), "cost": cost }));
+        } else {
+            println!();
+            match cost {
+                Some(cost) => println!("({},{}) reachable: true, cost: {}", target.0, target.1, cost),
+                None => println!("({},{}) reachable: false", target.0, target.1),
+            }
+        }
+        return;
+    }
+
+    if args.cost_histogram {
+        if json_output {
+            println!("{}", histogram_json(&grid, start, args.include_start));
+            return;
+        }
+        let histogram = grid.cost_histogram(start, args.include_start);
+        let reachable: usize = histogram.values().sum();
+        let open_cells = grid.width * grid.height - grid.walls.len();
+        println!();
+        println!("Reachability by cost ({} distinct levels):", histogram.len());
+        for (cost, cells) in &histogram {
+            println!("cost {:>6}: {} cell{}", cost, cells, if *cells == 1 { "" } else { "s" });
+        }
+        println!("Reachable: {}/{} open cells", reachable, open_cells);
+        return;
+    }
+
+    let (objective, label) = if args.minimax {
+        (Objective::Minimax, "MINIMAX")
+    } else {
+        (Objective::MinSum, "MINIMUM")
+    };
 
     if args.animate {
         println!();
-        println!("Searching for minimum cost path...");
+        println!("Searching for {} cost path...", label.to_lowercase());
+    }
+
+    let search = SearchOptions {
+        objective,
+        algorithm: args.algorithm,
+        max_depth: args.max_depth,
+        include_start: args.include_start,
+        reverse: args.reverse_search,
+        animation: args.animate.then(|| Animation {
+            frame_delay: Duration::from_secs_f64(1.0 / args.fps as f64),
+            color: should_colorize(args.color),
+            clear_screen: should_colorize(args.color) && std::io::stdout().is_terminal(),
+        }),
+    };
+    let (min_path, min_cost, min_stats) = grid.dijkstra((start, end), search);
+
+    // JSON output reports a missing path as a null cost instead
+    if min_path.is_empty() && !json_output {
+        println!();
+        match args.max_depth {
+            Some(depth) => println!("No path reaches the goal within {} moves", depth),
+            None => println!("No path found"),
+        }
+        return;
     }
 
-    let (min_path, min_cost) = grid.dijkstra(false, args.animate);
+    if let Some(k) = args.paths {
+        let paths = grid.k_shortest_paths((start, end), search, k);
+
+        if json_output {
+            let output: Vec<Value> = paths
+                .iter()
+                .map(|(path, cost)| path_json(&grid, path, *cost, objective, args.include_start, args.verbose_json))
+                .collect();
+            println!("{}", Value::Array(output));
+            return;
+        }
+
+        for (n, (path, cost)) in paths.iter().enumerate() {
+            print!("{}", path_info(&grid, &format!("#{} {}", n + 1, label), path, *cost, objective, args.include_start, args.count_only));
+        }
+        if paths.len() < k {
+            println!();
+            println!("Only {} distinct paths exist", paths.len());
+        }
+        if args.stats {
+            print_stats(&min_stats);
+        }
+
+        // The map and image show the cheapest path, as without --paths
+        let cheapest = paths.first().map_or(&[][..], |(path, _)| path);
+        if args.visualize {
+            println!();
+            grid.visualize(cheapest, &[], should_colorize(args.color), args.border, args.gradient_steps as usize);
+        }
+        save_png(&grid, &args, cheapest, &[]);
+        return;
+    }
 
     if args.both {
         if args.animate {
             println!();
-            println!("Searching for maximum cost path...");
+            println!("Searching for max (minimax) cost path...");
+        }
+        let (max_path, max_cost, max_stats) = grid.dijkstra((start, end), SearchOptions { objective: Objective::Minimax, ..search });
+
+        if json_output {
+            let mut min = path_json(&grid, &min_path, min_cost, objective, args.include_start, args.verbose_json);
+            let mut max = path_json(&grid, &max_path, max_cost, Objective::Minimax, args.include_start, args.verbose_json);
+            if args.stats {
+                min["stats"] = stats_json(&min_stats);
+                max["stats"] = stats_json(&max_stats);
+            }
+            println!("{}", json!({ "min": min, "max": max }));
+            return;
         }
-        let (max_path, max_cost) = grid.dijkstra(true, args.animate);
 
-        print_path_info("MINIMUM", &min_path, min_cost);
-        print_path_info("MAXIMUM", &max_path, max_cost);
+        print!("{}", path_info(&grid, label, &min_path, min_cost, objective, args.include_start, args.count_only));
+        if args.stats {
+            print_stats(&min_stats);
+        }
+        print!("{}", path_info(&grid, "MAXIMUM (MINIMAX)", &max_path, max_cost, Objective::Minimax, args.include_start, args.count_only));
+        if args.stats {
+            print_stats(&max_stats);
+        }
 
         if args.visualize {
             println!();
-            grid.visualize(&min_path, &max_path);
+            grid.visualize(&min_path, &max_path, should_colorize(args.color), args.border, args.gradient_steps as usize);
         }
+        save_png(&grid, &args, &min_path, &max_path);
     } else {
-        print_path_info("MINIMUM", &min_path, min_cost);
+        if json_output {
+            let mut output = path_json(&grid, &min_path, min_cost, objective, args.include_start, args.verbose_json);
+            if args.stats {
+                output["stats"] = stats_json(&min_stats);
+            }
+            println!("{}", output);
+            return;
+        }
+
+        print!("{}", path_info(&grid, label, &min_path, min_cost, objective, args.include_start, args.count_only));
+        if args.stats {
+            print_stats(&min_stats);
+        }
 
         if args.visualize {
             println!();
-            grid.visualize(&min_path, &[]);
+            grid.visualize(&min_path, &[], should_colorize(args.color), args.border, args.gradient_steps as usize);
+        }
+        save_png(&grid, &args, &min_path, &[]);
+    }
+}
+
+/// Handle --png, reporting (not panicking on) write failures
+fn save_png(grid: &Grid, args: &Args, min_path: &[Pos], max_path: &[Pos]) {
+    if let Some(png_file) = &args.png {
+        match grid.render_png(png_file, min_path, max_path, args.cell_size, args.gradient_steps as usize) {
+            Ok(()) => println!("Image saved to: {}", png_file),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1 2 3 / 4 5 6 / 7 8 9: the cheapest corner-to-corner route goes right then down
+    const SAMPLE: &str = "01 02 03\n04 05 06\n07 08 09\n";
+
+    fn grid(text: &str) -> Grid {
+        text.parse().unwrap()
+    }
+
+    fn corners(grid: &Grid) -> (Pos, Pos) {
+        (Pos(0, 0), Pos(grid.height - 1, grid.width - 1))
+    }
+
+    fn options() -> SearchOptions {
+        SearchOptions {
+            objective: Objective::MinSum,
+            algorithm: Algorithm::Dijkstra,
+            max_depth: None,
+            include_start: false,
+            reverse: false,
+            animation: None,
+        }
+    }
+
+    #[test]
+    fn max_depth_fails_below_the_shortest_route_and_succeeds_at_it() {
+        let grid = grid(SAMPLE);
+
+        // Corner to corner takes 4 moves at least
+        let (path, _, _) = grid.dijkstra(corners(&grid), SearchOptions { max_depth: Some(3), ..options() });
+        assert!(path.is_empty());
+
+        let (path, cost, _) = grid.dijkstra(corners(&grid), SearchOptions { max_depth: Some(4), ..options() });
+        assert_eq!(path.len(), 5);
+        assert_eq!(cost, 2 + 3 + 6 + 9);
+    }
+
+    #[test]
+    fn count_only_drops_the_path_but_keeps_cost_and_length() {
+        let grid = grid(SAMPLE);
+        let (path, cost, _) = grid.dijkstra(corners(&grid), options());
+
+        let full = path_info(&grid, "MINIMUM", &path, cost, Objective::MinSum, false, false);
+        assert!(full.contains("(0,0)-(0,1)-(0,2)-(1,2)-(2,2)"));
+
+        let counted = path_info(&grid, "MINIMUM", &path, cost, Objective::MinSum, false, true);
+        assert!(counted.contains("Total cost: 0x14 (20 decimal)"));
+        assert!(counted.contains("Path length: 5 steps"));
+        assert!(!counted.contains("(0,1)"));
+        assert!(!counted.contains("Step-by-step"));
+    }
+
+    #[test]
+    fn include_start_adds_the_start_cell_value() {
+        let grid = grid(SAMPLE);
+
+        let (_, without, _) = grid.dijkstra(corners(&grid), options());
+        let (_, with, _) = grid.dijkstra(corners(&grid), SearchOptions { include_start: true, ..options() });
+        assert_eq!(without, 20);
+        assert_eq!(with, 20 + 0x01);
+    }
+
+    /// Scratch file in the system temp directory, unique to this test process
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hexpath_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn distance_csv_matches_the_grid_and_known_distances() {
+        let grid = grid(SAMPLE);
+        let path = temp_path("distances.csv");
+        grid.save_distance_csv(path.to_str().unwrap(), Pos(0, 0), false).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let rows: Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), grid.height);
+        assert!(rows.iter().all(|row| row.len() == grid.width));
+        assert_eq!(rows[0], ["0", "2", "5"]);
+        assert_eq!(rows[1][0], "4");
+        assert_eq!(rows[2][2], "20");
+    }
+
+    #[test]
+    fn color_always_and_never_override_detection() {
+        assert!(should_colorize(ColorChoice::Always));
+        assert!(!should_colorize(ColorChoice::Never));
+    }
+
+    #[test]
+    fn minimax_takes_the_detour_around_the_expensive_cell() {
+        // Straight across costs 5 in total but crosses the 05; the detour's highest cell is 03
+        let grid = grid("00 05 00\n03 03 03\n");
+        let endpoints = (Pos(0, 0), Pos(0, 2));
+
+        let (path, cost, _) = grid.dijkstra(endpoints, options());
+        assert_eq!((cost, path.len()), (5, 3));
+
+        let (path, cost, _) = grid.dijkstra(endpoints, SearchOptions { objective: Objective::Minimax, ..options() });
+        assert_eq!(cost, 3);
+        let cells: Vec<_> = path.iter().map(|pos| (pos.0, pos.1)).collect();
+        assert_eq!(cells, [(0, 0), (1, 0), (1, 1), (1, 2), (0, 2)]);
+    }
+
+    #[test]
+    fn border_frames_the_map_with_indexes() {
+        let map = grid(SAMPLE).render_map(&[], &[], false, true, 16);
+        let lines: Vec<&str> = map.lines().collect();
+        assert_eq!(lines[2], "    0  1  2");
+        assert!(lines[3].starts_with("  ┌"));
+        assert!(lines[4].starts_with("0 │ 01 "));
+        assert!(lines[6].starts_with("2 │ 07 "));
+        assert!(lines[7].starts_with("  └"));
+    }
+
+    #[test]
+    fn generated_corners_take_the_pinned_values() {
+        let grid = Grid::generate_random(4, 3, 0..=255, Some(0x12), Some(0x34));
+        assert_eq!(grid.cells[0][0], 0x12);
+        assert_eq!(grid.cells[2][3], 0x34);
+
+        assert_eq!(corner_pin(Some("ab"), 0x00, false), Ok(Some(0xAB)));
+        assert_eq!(corner_pin(Some("0x7f"), 0x00, true), Ok(Some(0x7F)));
+        assert_eq!(corner_pin(None, 0xFF, false), Ok(Some(0xFF)));
+        assert_eq!(corner_pin(None, 0xFF, true), Ok(None));
+        assert!(corner_pin(Some("100"), 0x00, false).is_err());
+    }
+
+    #[test]
+    fn neighbor_counts_depend_on_position_and_diagonal() {
+        let mut grid = grid(SAMPLE);
+        let counts = |grid: &Grid| [Pos(1, 1), Pos(0, 1), Pos(0, 0)].map(|pos| grid.neighbors(pos).len());
+        assert_eq!(counts(&grid), [4, 3, 2]);
+        grid.diagonal = true;
+        assert_eq!(counts(&grid), [8, 5, 3]);
+    }
+
+    #[test]
+    fn map_files_over_the_size_limit_are_refused() {
+        let path = temp_path("oversized.txt");
+        fs::write(&path, SAMPLE).unwrap();
+        let filename = path.to_str().unwrap();
+        let refused = Grid::from_file(filename, 3).err();
+        let loaded = Grid::from_file(filename, SAMPLE.len() as u64).map(|grid| grid.cells);
+        fs::remove_file(&path).unwrap();
+
+        assert!(refused.unwrap().contains("over the 3 byte limit"));
+        assert_eq!(loaded.unwrap(), grid(SAMPLE).cells);
+    }
+
+    #[test]
+    fn gradient_steps_set_the_number_of_colors() {
+        let distinct = |steps| gradient_palette(steps).into_iter().collect::<HashSet<_>>().len();
+        assert_eq!(gradient_palette(4).len(), 4);
+        assert!(distinct(16) > distinct(4));
+    }
+
+    #[test]
+    fn reverse_search_matches_the_forward_cost() {
+        for text in [SAMPLE, "00 05 00\n03 03 03\n", "01 FF 01\n01 10 01\n20 01 01\n"] {
+            let grid = grid(text);
+            let endpoints = corners(&grid);
+            let (forward, forward_cost, _) = grid.dijkstra(endpoints, options());
+            let (backward, backward_cost, _) = grid.dijkstra(endpoints, SearchOptions { reverse: true, ..options() });
+            assert_eq!(forward_cost, backward_cost);
+            assert!(backward.first() == Some(&endpoints.0) && backward.last() == Some(&endpoints.1));
+            assert_eq!(forward.len(), backward.len());
+        }
+    }
+
+    #[test]
+    fn histogram_counts_cells_per_distance() {
+        let histogram = grid("00 01\n01 05\n").cost_histogram(Pos(0, 0), false);
+        assert_eq!(histogram, BTreeMap::from([(0, 1), (1, 2), (6, 1)]));
+    }
+
+    #[test]
+    fn probe_reports_reachability_and_cost() {
+        let grid = grid("00 01 XX\n01 XX 00\nXX 00 00\n");
+        assert_eq!(grid.probe(Pos(0, 0), Pos(1, 0), false), Some(1));
+        assert_eq!(grid.probe(Pos(0, 0), Pos(1, 0), true), Some(1));
+        assert_eq!(grid.probe(Pos(0, 0), Pos(2, 2), false), None);
+    }
+
+    #[test]
+    fn diagonal_moves_cut_across_the_grid() {
+        let mut grid = grid("01 01 01\n01 01 01\n01 01 01\n");
+        let endpoints = corners(&grid);
+        let (path, cost, _) = grid.dijkstra(endpoints, options());
+        assert_eq!((path.len(), cost), (5, 4));
+
+        grid.diagonal = true;
+        let (path, cost, _) = grid.dijkstra(endpoints, options());
+        assert_eq!((path.len(), cost), (3, 2));
+        assert_eq!((path[1].0, path[1].1), (1, 1));
+    }
+
+    #[test]
+    fn max_path_minimizes_the_largest_cell() {
+        let grid = grid("00 09 01\n02 03 02\n01 05 01\n");
+        let endpoints = corners(&grid);
+
+        let (path, cost, _) = grid.dijkstra(endpoints, SearchOptions { objective: Objective::Minimax, ..options() });
+        assert_eq!(cost, 3);
+        let highest = path[1..].iter().filter_map(|&pos| grid.get(pos)).max();
+        assert_eq!(highest, Some(3));
+
+        let (_, cost, _) = grid.dijkstra(endpoints, options());
+        assert_eq!(cost, 8);
+    }
+
+    #[test]
+    fn jagged_rows_are_rejected() {
+        let err = "01 02\n03\n".parse::<Grid>().err();
+        assert_eq!(err.as_deref(), Some("Row 2 (line 2) has 1 columns, expected 2"));
+
+        // Blank lines are skipped but still counted for the line number
+        let err = "01 02\n\n03 04 05\n".parse::<Grid>().err();
+        assert_eq!(err.as_deref(), Some("Row 2 (line 3) has 3 columns, expected 2"));
+    }
+
+    #[test]
+    fn astar_finds_the_dijkstra_cost() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut grid = Grid::new(7, 5);
+            for cell in grid.cells.iter_mut().flatten() {
+                *cell = rng.gen();
+            }
+            let endpoints = corners(&grid);
+            for diagonal in [false, true] {
+                grid.diagonal = diagonal;
+                let (_, dijkstra, _) = grid.dijkstra(endpoints, options());
+                let (_, astar, _) = grid.dijkstra(endpoints, SearchOptions { algorithm: Algorithm::Astar, ..options() });
+                assert_eq!(dijkstra, astar, "seed {} diagonal {}", seed, diagonal);
+            }
+        }
+    }
+
+    #[test]
+    fn json_breakdown_ends_at_the_path_cost() {
+        let grid = grid(SAMPLE);
+        let (path, cost, _) = grid.dijkstra(corners(&grid), options());
+        let value = path_json(&grid, &path, cost, Objective::MinSum, false, true);
+        assert_eq!(value["cost"], 20);
+        assert_eq!(value["length"], 5);
+        assert_eq!(value["path"][0], json!([0, 0]));
+        assert_eq!(value["breakdown"][4]["cumulative"], 20);
+
+        let value = path_json(&grid, &[], 0, Objective::MinSum, false, false);
+        assert!(value["cost"].is_null() && value.get("breakdown").is_none());
+    }
+
+    #[test]
+    fn maps_parse_from_text_with_walls() {
+        let grid = grid("0a xx\n  FF 00  \n\n");
+        assert_eq!((grid.width, grid.height), (2, 2));
+        assert_eq!(grid.cells, [[0x0A, 0], [0xFF, 0]]);
+        assert!(grid.is_wall(Pos(0, 1)) && !grid.is_wall(Pos(1, 1)));
+        assert_eq!("".parse::<Grid>().err().as_deref(), Some("Empty map"));
+        assert!("0G".parse::<Grid>().is_err());
+    }
+
+    #[test]
+    fn palette_index_covers_the_gradient() {
+        assert_eq!(palette_index(0x00, 7), 0);
+        assert_eq!(palette_index(0xFF, 7), 6);
+        assert_eq!(palette_index(0x80, 2), 1);
+        assert_eq!(palette_index(0xFF, 256), 255);
+        assert_eq!(palette_index(0x7F, 1), 0);
+    }
+
+    #[test]
+    fn step_costs_add_up_to_the_total() {
+        let grid = grid(SAMPLE);
+        let (path, cost, _) = grid.dijkstra(corners(&grid), options());
+        let info = path_info(&grid, "MINIMUM", &path, cost, Objective::MinSum, false, false);
+        assert!(info.contains("- 0x01 (0,0) +0 = 0\n"));
+        assert!(info.contains("- 0x09 (2,2) +9 = 20\n"));
+
+        let breakdown = cost_breakdown(&grid, &path, Objective::MinSum, true);
+        assert_eq!(breakdown.last().map(|step| step.2), Some(21));
+    }
+
+    #[test]
+    fn stats_count_pops_and_relaxations() {
+        let grid = grid("00 01\n");
+        let (path, cost, stats) = grid.dijkstra(corners(&grid), options());
+        assert_eq!((cost, path.len()), (1, 2));
+        assert_eq!((stats.popped, stats.relaxations), (2, 1));
+
+        // Timing aside, repeated runs explore the same way
+        let sample = self::grid(SAMPLE);
+        let first = sample.dijkstra(corners(&sample), options());
+        let second = sample.dijkstra(corners(&sample), options());
+        assert!(first.0 == second.0 && first.2.popped == second.2.popped);
+    }
+
+    #[test]
+    fn generated_values_stay_in_range() {
+        let grid = Grid::generate_random(6, 5, 0x10..=0x10, None, None);
+        assert_eq!((grid.width, grid.height), (6, 5));
+        assert!(grid.cells.iter().flatten().all(|&cell| cell == 0x10));
+
+        let grid = Grid::generate_random(8, 8, 0x20..=0x2F, None, None);
+        assert!(grid.cells.iter().flatten().all(|cell| (0x20..=0x2F).contains(cell)));
+    }
+
+    #[test]
+    fn custom_endpoints_are_parsed_and_checked() {
+        let sample = grid(SAMPLE);
+        let (start, end) = search_endpoints(Some("1, 0"), Some("0,2"), &sample).unwrap();
+        assert_eq!((start.0, start.1, end.0, end.1), (1, 0, 0, 2));
+        let (start, end) = search_endpoints(None, None, &sample).unwrap();
+        assert_eq!((start.0, start.1, end.0, end.1), (0, 0, 2, 2));
+
+        let err = search_endpoints(Some("3,0"), None, &sample).err();
+        assert_eq!(err.as_deref(), Some("(3,0) is outside the 3x3 grid"));
+        assert!(search_endpoints(Some("1"), None, &sample).is_err());
+
+        let walled = grid("00 XX\n00 00\n");
+        let err = search_endpoints(None, Some("0,1"), &walled).err();
+        assert_eq!(err.as_deref(), Some("End cell (0,1) is a wall"));
+    }
+
+    #[test]
+    fn walls_are_never_entered() {
+        // The cheap middle column is walled off except at the bottom
+        let grid = grid("00 XX 00\n09 XX 09\n09 01 09\n");
+        let (path, cost, _) = grid.dijkstra((Pos(0, 0), Pos(0, 2)), options());
+        assert!(path.iter().all(|&pos| !grid.is_wall(pos)));
+        assert_eq!((cost, path.len()), (9 + 9 + 1 + 9 + 9, 7));
+
+        let sealed = self::grid("00 XX\nXX 00\n");
+        let (path, _, _) = sealed.dijkstra(corners(&sealed), options());
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn json_histogram_lists_levels_and_counts() {
+        let value = histogram_json(&grid("00 01\nXX 05\n"), Pos(0, 0), false);
+        assert_eq!(value, json!({
+            "levels": [{ "cost": 0, "cells": 1 }, { "cost": 1, "cells": 1 }, { "cost": 6, "cells": 1 }],
+            "reachable": 3,
+            "open_cells": 3,
+        }));
+    }
+
+    #[test]
+    fn json_path_round_trips_through_text() {
+        let grid = grid(SAMPLE);
+        let (path, cost, _) = grid.dijkstra((Pos(2, 0), Pos(0, 2)), options());
+        let text = path_json(&grid, &path, cost, Objective::MinSum, false, false).to_string();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed["cost"], cost);
+        assert_eq!(parsed["path"][0], json!([2, 0]));
+        assert_eq!(parsed["path"][path.len() - 1], json!([0, 2]));
+        assert_eq!(parsed["length"], path.len());
+    }
+
+    #[test]
+    fn k_shortest_paths_are_distinct_and_ordered() {
+        let grid = grid(SAMPLE);
+        let paths = grid.k_shortest_paths(corners(&grid), options(), 4);
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths[0].1, 20);
+        assert!(paths.windows(2).all(|pair| pair[0].1 <= pair[1].1 && pair[0].0 != pair[1].0));
+        for (path, cost) in &paths {
+            assert_eq!(cost_breakdown(&grid, path, Objective::MinSum, false).last().unwrap().2, *cost);
         }
+
+        // A 3x3 grid has only six monotone routes, and loops are never added
+        let all = grid.k_shortest_paths(corners(&grid), SearchOptions { max_depth: Some(4), ..options() }, 10);
+        assert_eq!(all.len(), 6);
+
+        assert!(Args::try_parse_from(["hexpath", "map.txt", "--paths", "0"]).is_err());
+        assert_eq!(Args::try_parse_from(["hexpath", "map.txt", "--paths", "2"]).unwrap().paths, Some(2));
+    }
+
+    #[test]
+    fn png_render_scales_cells_and_marks_the_path() {
+        let grid = grid("00 XX\n80 FF\n");
+        let path = temp_path("render.png");
+        let filename = path.to_str().unwrap();
+        grid.render_png(filename, &[Pos(0, 0)], &[Pos(1, 1)], 4, 16).unwrap();
+        let image = image::open(&path).unwrap().to_rgb8();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (8, 8));
+        assert_eq!(image.get_pixel(3, 3).0, [255, 255, 255]);
+        assert_eq!(image.get_pixel(4, 0).0, [88, 88, 88]);
+        assert_eq!(image.get_pixel(7, 7).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(0, 4), image.get_pixel(3, 7));
+    }
+
+    #[test]
+    fn animation_frame_marks_current_frontier_and_visited() {
+        let grid = grid("00 01 02\n");
+        let animation = Animation { frame_delay: Duration::ZERO, color: false, clear_screen: false };
+        let visited = HashSet::from([Pos(0, 0)]);
+        let frontier = HashSet::from([Pos(0, 2)]);
+        let frame = grid.render_search_frame(Pos(0, 1), &visited, &frontier, animation);
+        assert_eq!(frame, "\n(@ = current, ? = frontier, . = visited)\n00.01@02?\n");
+
+        let animation = Animation { color: true, clear_screen: true, ..animation };
+        assert!(grid.render_search_frame(Pos(0, 1), &visited, &frontier, animation).starts_with("\x1b[2J\x1b[H"));
     }
 }